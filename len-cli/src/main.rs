@@ -1,10 +1,8 @@
-use eyre::Result;
-use len::{
-    ast, chumsky,
-    eval::RawScope,
-    lexer::{lexer, Token},
-};
-use std::io::{self, Write};
+use eyre::{Result, WrapErr};
+use len::complete::Context;
+use len::diagnostic::Style;
+use len::source::Sources;
+use std::io::{self, IsTerminal, Write};
 use tracing::info;
 use tracing_subscriber::{prelude::*, EnvFilter};
 
@@ -25,20 +23,38 @@ fn main() -> Result<()> {
         .with(layer_fmt)
         .init();
 
+    let mut sources = Sources::new();
+    let mut context = Context::default();
+    let style = if io::stderr().is_terminal() {
+        Style::Ansi
+    } else {
+        Style::Plain
+    };
+
+    // Every file sees the bindings of the ones before it
+    let paths = std::env::args().skip(1).collect::<Vec<_>>();
+    if !paths.is_empty() {
+        for path in paths {
+            let input = std::fs::read_to_string(&path)
+                .wrap_err_with(|| format!("Failed to read {path}"))?;
+            let file = sources.add(path, input);
+            context = len::complete::complete(&sources, file, context, style);
+        }
+        return Ok(());
+    }
+
     let mut stdout = io::stdout();
     let stdin = io::stdin();
 
     info!("Welcome to the len repl");
 
-    let mut scope = None;
-
     loop {
         print!("len> ");
         stdout.flush().unwrap();
 
         let mut buf = String::new();
         let exit = stdin.read_line(&mut buf);
-        if !buf.contains("\n") {
+        if !buf.contains('\n') {
             println!();
         }
 
@@ -51,7 +67,8 @@ fn main() -> Result<()> {
                 err.unwrap();
             }
             Ok(_) => {
-                scope = Some(len::complete::complete(&buf, scope));
+                let file = sources.add("<repl>", buf);
+                context = len::complete::complete(&sources, file, context, style);
             }
         }
     }
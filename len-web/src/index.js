@@ -9,6 +9,12 @@ async function main() {
   const res = crate.main(input);
   // document.getElementById("output").textContent = res;
   // for all element with name output, set textContent to res
+  document.getElementsByName("output").forEach((e) => {
+    e.textContent = res.output;
+  });
+  document.getElementsByName("types").forEach((e) => {
+    e.textContent = res.types;
+  });
   document.getElementsByName("lexer").forEach((e) => {
     e.textContent = res.lexer;
   });
@@ -1,34 +1,77 @@
 mod utils;
 
 use chumsky::extra;
-use len::chumsky::{self, error::Rich, Parser};
+use len::chumsky::{self, error::Rich, input::Input, Parser};
+use len::diagnostic::{render, Diagnostic, Style};
+use len::eval::{eval_program, Scope};
+use len::infer::{check_program, Env};
+use len::source::Sources;
 use wasm_bindgen::prelude::*;
 
-type Extra<'a, T> = extra::Err<Rich<'a, T>>;
+type Extra<'a, T> = extra::Err<Rich<'a, T, len::lexer::Span>>;
 
 #[wasm_bindgen(getter_with_clone)]
 pub struct EvalResult {
+    /// The value of the program, or its diagnostics.
+    pub output: String,
+    /// The inferred type of every binding, and of the program.
+    pub types: String,
     pub lexer: String,
     pub ast: String,
 }
 
 #[wasm_bindgen]
 pub fn main(input: String) -> EvalResult {
-    let lexer_res = len::lexer::lexer::<Extra<_>>().parse(&input);
+    let mut sources = Sources::new();
+    let file = sources.add("<input>", input);
+    let input = sources.get(file).text();
+
+    let lexer_res = len::lexer::lexer::<Extra<_>>().parse(input.with_context(file.context()));
 
     let lexer_res_str = format!("{:#?}", lexer_res);
 
-    let prev = lexer_res
-        .into_output()
-        .unwrap_or_default()
-        .into_iter()
-        .map(|t| t.kind)
-        .collect::<Vec<_>>();
+    let prev = lexer_res.into_output().unwrap_or_default();
+    let eoi = file.context().span(input.len()..input.len());
 
-    let ast_res = len::ast::ast_parser::<Extra<_>>().parse(&prev);
+    let ast_res = len::ast::program_parser::<Extra<_>>().parse(prev.as_slice().spanned(eoi));
     let ast_res_str = format!("{:#?}", ast_res);
 
+    let mut types = String::new();
+    let mut warnings = Vec::new();
+    let diagnostics = match len::complete::parse(&sources, file) {
+        Ok(program) => match check_program(&program, &Env::std()) {
+            Ok(checked) => {
+                types = checked
+                    .bindings
+                    .iter()
+                    .map(|(name, scheme)| format!("{name} : {scheme}"))
+                    .chain(checked.body.map(|ty| ty.to_string()))
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                warnings = checked.warnings.iter().map(Diagnostic::from).collect();
+                match eval_program(program, Scope::std()) {
+                    Ok((_, value)) => Ok(value.map(|v| v.to_string()).unwrap_or_default()),
+                    Err(err) => Err(vec![Diagnostic::from(&err)]),
+                }
+            }
+            Err(err) => Err(vec![Diagnostic::from(&err)]),
+        },
+        Err(diagnostics) => Err(diagnostics),
+    };
+    let (diagnostics, value) = match diagnostics {
+        Ok(value) => (warnings, value),
+        Err(errors) => (warnings.into_iter().chain(errors).collect(), String::new()),
+    };
+    let output = diagnostics
+        .iter()
+        .map(|d| render(d, &sources, Style::Plain))
+        .chain((!value.is_empty()).then_some(value))
+        .collect::<Vec<_>>()
+        .join("\n");
+
     EvalResult {
+        output,
+        types,
         lexer: lexer_res_str,
         ast: ast_res_str,
     }
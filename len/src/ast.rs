@@ -1,36 +1,128 @@
-use crate::lexer::{Token, TokenKind};
-use crate::Int;
+use crate::lexer::{Span, Token, TokenKind};
+use crate::{Int, Rational};
 use chumsky::extra::ParserExtra;
-use chumsky::pratt::{infix, left, postfix, prefix};
+use chumsky::input::SpannedInput;
+use chumsky::pratt::{infix, left, postfix};
 use chumsky::prelude::*;
 use chumsky::Parser;
-use std::borrow::Cow;
-use std::collections::HashMap;
-use std::ops::Not;
-use tracing::span::Id;
-use tracing::{debug, trace};
-use tracing_test::traced_test;
+use std::collections::HashSet;
+use std::fmt;
+use tracing::trace;
 
 #[derive(Debug, Clone)]
 pub enum Ast {
     Expr(Expr),
-    Binding { lhs: Identifier, rhs: Expr },
+    Binding(Binding),
+    TypeDecl(TypeDecl),
     Todo,
 }
 
+/// `lhs = rhs`, or `lhs : annotation = rhs`
 #[derive(Debug, Clone)]
-pub enum Expr {
+pub struct Binding {
+    pub lhs: Identifier,
+    pub annotation: Option<TypeExpr>,
+    pub rhs: Expr,
+}
+
+/// A whole source file: a sequence of bindings, optionally followed by an
+/// expression that gives the program its value.
+#[derive(Debug, Clone)]
+pub struct Program {
+    pub types: Vec<TypeDecl>,
+    pub bindings: Vec<Binding>,
+    pub body: Option<Expr>,
+}
+
+impl Program {
+    /// Gathers the parsed statements of a program, reporting the
+    /// expressions that aren't last and the names that are defined twice.
+    pub fn from_statements<'s>(statements: Vec<Ast>) -> (Self, Vec<Rich<'s, TokenKind<'s>, Span>>) {
+        let mut errors = Vec::new();
+        let mut program = Program {
+            types: Vec::new(),
+            bindings: Vec::new(),
+            body: None,
+        };
+        let last = statements.len().saturating_sub(1);
+
+        for (i, statement) in statements.into_iter().enumerate() {
+            match statement {
+                Ast::Binding(binding) => program.bindings.push(binding),
+                Ast::TypeDecl(decl) => program.types.push(decl),
+                Ast::Expr(expr) if i == last => program.body = Some(expr),
+                Ast::Expr(expr) => errors.push(Rich::custom(
+                    expr.span,
+                    "only the last statement of a program can be an expression",
+                )),
+                Ast::Todo => {}
+            }
+        }
+
+        for name in duplicates(program.bindings.iter().map(|b| &b.lhs)) {
+            errors.push(Rich::custom(
+                name.span,
+                format!("`{}` is defined more than once", name.name),
+            ));
+        }
+        for name in duplicates(program.types.iter().map(|t| &t.name)) {
+            errors.push(Rich::custom(
+                name.span,
+                format!("type `{}` is defined more than once", name.name),
+            ));
+        }
+        let constructors = program.types.iter().flat_map(|t| &t.variants);
+        for name in duplicates(constructors.map(|v| &v.name)) {
+            errors.push(Rich::custom(
+                name.span,
+                format!("constructor `{}` is defined more than once", name.name),
+            ));
+        }
+
+        (program, errors)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum ExprKind {
     Literal(Literal),
     FunctionCall(FunctionCall),
     Identifier(Identifier),
-    Product(HashMap<String, Expr>),
+    /// Fields in source order, the parser rejects duplicated names.
+    Product(Vec<(Identifier, Expr)>),
+    /// `(a, b)`, always with two or more elements
+    Tuple(Vec<Expr>),
+    FieldAccess(FieldAccess),
+    Extension(Extension),
+    Update(Update),
     Lambda(Lambda),
+    If(If),
+    Let(Let),
+    Match(Match),
+    Ascription(Ascription),
+    /// `"a ${b} c"`, with at least one embedded expression.
+    Interpolation(Vec<StringPart>),
     Todo,
 }
 
+#[derive(Debug, Clone)]
+pub enum StringPart {
+    Text(String),
+    /// An expression whose value is written into the string.
+    Expr(Expr),
+}
+
 #[derive(Debug, Clone)]
 pub enum Literal {
     Integer(Int),
+    /// Boxed, as it is twice the size of the other literals.
+    Decimal(Box<Rational>),
     String(String),
     Boolean(bool),
 }
@@ -41,131 +133,901 @@ pub struct FunctionCall {
     pub(crate) argument: Box<Expr>,
 }
 
+/// `record.field`
+#[derive(Debug, Clone)]
+pub struct FieldAccess {
+    pub record: Box<Expr>,
+    pub field: Identifier,
+}
+
+/// `{ c: 3 | record }`, a copy of `record` with more fields
+#[derive(Debug, Clone)]
+pub struct Extension {
+    pub fields: Vec<(Identifier, Expr)>,
+    pub record: Box<Expr>,
+}
+
+/// `{ record | a: 2 }`, a copy of `record` with some of its fields replaced
+#[derive(Debug, Clone)]
+pub struct Update {
+    pub record: Box<Expr>,
+    pub fields: Vec<(Identifier, Expr)>,
+}
+
 #[derive(Debug, Clone)]
 pub struct Identifier {
     pub(crate) name: String,
+    pub span: Span,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct Lambda {
-    pub from: Identifier,
+    pub from: Pattern,
     pub to: Box<Expr>,
+    /// Whether this lambda comes from the parameters of its parent, as `b`
+    /// in `a b => body`.
+    pub curried: bool,
 }
 
-#[derive(Debug, PartialEq)]
-pub struct Spanned2<T>(T, SimpleSpan<usize>);
-
-pub fn expression_parser<'s, E: ParserExtra<'s, &'s [TokenKind<'s>]>>(
-) -> impl Parser<'s, &'s [TokenKind<'s>], Expr, extra::Err<Rich<'s, TokenKind<'s>>>> + Clone {
-    recursive(|expr| {
-        let literal = select! {
-            TokenKind::Ident("true") => Literal::Boolean(true),
-            TokenKind::Ident("false") => Literal::Boolean(false),
-            TokenKind::Number(x) => Literal::Integer(x),
-            TokenKind::String(x) => Literal::String(x.to_string()),
+/// Prints `a b => body` as written, instead of the lambdas it is made of.
+impl fmt::Debug for Lambda {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parameters = vec![&self.from];
+        let mut body = &*self.to;
+        while let ExprKind::Lambda(lambda @ Lambda { curried: true, .. }) = &body.kind {
+            parameters.push(&lambda.from);
+            body = &lambda.to;
         }
-        .map(Expr::Literal);
 
-        let ident = select! {
-            TokenKind::Ident(s) => Identifier { name: s.to_string() },
+        f.debug_struct("Lambda")
+            .field("parameters", &parameters)
+            .field("body", body)
+            .finish()
+    }
+}
+
+/// `if condition then then_branch else else_branch`
+#[derive(Debug, Clone)]
+pub struct If {
+    pub condition: Box<Expr>,
+    pub then_branch: Box<Expr>,
+    pub else_branch: Box<Expr>,
+}
+
+/// `let a = 1; b = 2 in body`
+#[derive(Debug, Clone)]
+pub struct Let {
+    pub bindings: Vec<Binding>,
+    pub body: Box<Expr>,
+}
+
+/// `expr : ty`
+#[derive(Debug, Clone)]
+pub struct Ascription {
+    pub expr: Box<Expr>,
+    pub ty: Box<TypeExpr>,
+}
+
+/// `match scrutinee { pattern => body, ... }`
+#[derive(Debug, Clone)]
+pub struct Match {
+    pub scrutinee: Box<Expr>,
+    pub arms: Vec<MatchArm>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub body: Expr,
+}
+
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    pub kind: PatternKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum PatternKind {
+    /// `_`
+    Wildcard,
+    Variable(Identifier),
+    Literal(Literal),
+    /// `None`, `Some x`
+    Constructor(Identifier, Option<Box<Pattern>>),
+    /// `{ a: pattern, b }`, where `b` is short for `b: b`. Other fields of
+    /// the record are ignored.
+    Record(Vec<(Identifier, Pattern)>),
+    /// `(a, b)`
+    Tuple(Vec<Pattern>),
+    /// `(pattern : Int)`
+    Annotated(Box<Pattern>, Box<TypeExpr>),
+}
+
+impl Pattern {
+    /// The variables bound by the pattern, from left to right.
+    pub fn variables(&self) -> Vec<&Identifier> {
+        match &self.kind {
+            PatternKind::Wildcard | PatternKind::Literal(_) => Vec::new(),
+            PatternKind::Variable(name) => vec![name],
+            PatternKind::Constructor(_, payload) => {
+                payload.iter().flat_map(|p| p.variables()).collect()
+            }
+            PatternKind::Record(fields) => fields.iter().flat_map(|(_, p)| p.variables()).collect(),
+            PatternKind::Tuple(elements) => elements.iter().flat_map(|p| p.variables()).collect(),
+            PatternKind::Annotated(pattern, _) => pattern.variables(),
         }
-        .map(Expr::Identifier);
+    }
+}
+
+/// `type Option a = Some a | None`
+#[derive(Debug, Clone)]
+pub struct TypeDecl {
+    pub name: Identifier,
+    pub parameters: Vec<Identifier>,
+    pub variants: Vec<Variant>,
+}
+
+/// A constructor of a sum type, holding at most one value.
+#[derive(Debug, Clone)]
+pub struct Variant {
+    pub name: Identifier,
+    pub payload: Option<TypeExpr>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TypeExpr {
+    pub kind: TypeExprKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum TypeExprKind {
+    /// A lowercase name, `a`
+    Variable(Identifier),
+    /// A type constructor applied to its arguments, `Option Int`
+    Named(Identifier, Vec<TypeExpr>),
+    /// `{ a: Int, b: String }`, or `{ a: Int | r }` for the records that
+    /// have at least the field `a`
+    Record {
+        fields: Vec<(Identifier, TypeExpr)>,
+        rest: Option<Identifier>,
+    },
+    /// `a -> b`
+    Function(Box<TypeExpr>, Box<TypeExpr>),
+    /// `(a, b)`
+    Tuple(Vec<TypeExpr>),
+    /// `Eq a => a -> a -> Bool`, only at the start of the annotation of a
+    /// binding
+    Constrained {
+        constraints: Vec<Constraint>,
+        ty: Box<TypeExpr>,
+    },
+}
+
+/// `Eq a`, the type variable `a` being in the class `Eq`
+#[derive(Debug, Clone)]
+pub struct Constraint {
+    pub class: Identifier,
+    pub variable: Identifier,
+}
+
+/// Uppercase names are constructors, of values and of types.
+pub fn is_constructor(name: &str) -> bool {
+    name.starts_with(char::is_uppercase)
+}
+
+/// Every name that was already seen earlier in `names`.
+fn duplicates<'a>(names: impl IntoIterator<Item = &'a Identifier>) -> Vec<&'a Identifier> {
+    let mut seen = HashSet::new();
+    names
+        .into_iter()
+        .filter(|name| !seen.insert(name.name.as_str()))
+        .collect()
+}
+
+/// The parsers run over the output of the lexer, keeping the span of every
+/// token so that they end up in the AST.
+pub type ParserInput<'s> = SpannedInput<TokenKind<'s>, Span, &'s [Token<'s>]>;
+
+fn literal_parser<'s>(
+) -> impl Parser<'s, ParserInput<'s>, Literal, extra::Err<Rich<'s, TokenKind<'s>, Span>>> + Clone {
+    select! {
+        TokenKind::Ident("true") => Literal::Boolean(true),
+        TokenKind::Ident("false") => Literal::Boolean(false),
+        TokenKind::Number(x) => Literal::Integer(x),
+        TokenKind::Decimal(x) => Literal::Decimal(Box::new(x)),
+        TokenKind::String(x) => Literal::String(x),
+    }
+}
+
+/// Patterns that don't need parentheses to be a parameter of a lambda: all
+/// but constructors with a payload. `pattern` parses the nested patterns.
+fn pattern_atom_parser<'s, P>(
+    pattern: P,
+) -> impl Parser<'s, ParserInput<'s>, Pattern, extra::Err<Rich<'s, TokenKind<'s>, Span>>> + Clone
+where
+    P: Parser<'s, ParserInput<'s>, Pattern, extra::Err<Rich<'s, TokenKind<'s>, Span>>> + Clone,
+{
+    let variable = select! {
+        TokenKind::Ident(s) = e if !is_constructor(s) => Identifier { name: s.to_string(), span: e.span() },
+    };
+
+    let constructor = select! {
+        TokenKind::Ident(s) = e if is_constructor(s) => Identifier { name: s.to_string(), span: e.span() },
+    };
+
+    let field = variable
+        .then(just(TokenKind::Colon).ignore_then(pattern.clone()).or_not())
+        .map(|(name, pattern): (Identifier, Option<Pattern>)| {
+            let pattern = pattern.unwrap_or_else(|| Pattern {
+                kind: PatternKind::Variable(name.clone()),
+                span: name.span,
+            });
+            (name, pattern)
+        });
 
-        let grouping = expr.clone().delimited_by(
+    let record = field
+        .separated_by(just(TokenKind::Comma))
+        .allow_trailing()
+        .collect::<Vec<_>>()
+        .validate(|fields: Vec<(Identifier, Pattern)>, _, emitter| {
+            for name in duplicates(fields.iter().map(|(name, _)| name)) {
+                emitter.emit(Rich::custom(
+                    name.span,
+                    format!("field `{}` is matched more than once", name.name),
+                ));
+            }
+            fields
+        })
+        .delimited_by(just(TokenKind::LeftCurly), just(TokenKind::RightCurly))
+        .map(PatternKind::Record);
+
+    let annotated = pattern
+        .then(
+            just(TokenKind::Colon)
+                .ignore_then(type_parser::<extra::Err<Rich<'s, TokenKind<'s>, Span>>>())
+                .or_not(),
+        )
+        .map_with(|(pattern, ty), e| match ty {
+            Some(ty) => Pattern {
+                kind: PatternKind::Annotated(Box::new(pattern), Box::new(ty)),
+                span: e.span(),
+            },
+            None => pattern,
+        });
+
+    // `(p)` is just `p`, which may have a type annotation
+    let grouping = annotated
+        .separated_by(just(TokenKind::Comma))
+        .at_least(1)
+        .collect::<Vec<_>>()
+        .delimited_by(
             just(TokenKind::LeftParenthesis),
             just(TokenKind::RightParenthesis),
-        );
+        )
+        .map(|mut elements: Vec<Pattern>| match elements.len() {
+            1 => elements.remove(0).kind,
+            _ => PatternKind::Tuple(elements),
+        });
+
+    choice((
+        just(TokenKind::Underscore).to(PatternKind::Wildcard),
+        literal_parser().map(PatternKind::Literal),
+        variable.map(PatternKind::Variable),
+        constructor.map(|name| PatternKind::Constructor(name, None)),
+        record,
+        grouping,
+    ))
+    .map_with(|kind, e| Pattern {
+        kind,
+        span: e.span(),
+    })
+    .labelled("pattern")
+}
+
+/// Patterns, where a constructor takes a single pattern for its payload, so
+/// nested constructors need parentheses: `Some (Some x)`.
+pub fn pattern_parser<'s, E: ParserExtra<'s, ParserInput<'s>>>(
+) -> impl Parser<'s, ParserInput<'s>, Pattern, extra::Err<Rich<'s, TokenKind<'s>, Span>>> + Clone {
+    let pattern = recursive(|pattern| {
+        let constructor = select! {
+            TokenKind::Ident(s) = e if is_constructor(s) => Identifier { name: s.to_string(), span: e.span() },
+        };
+
+        let atom = pattern_atom_parser(pattern);
 
-        let struct_elem = select! {
-            TokenKind::Ident(s) => s.to_string(),
+        constructor
+            .then(atom.clone())
+            .map_with(|(name, payload), e| Pattern {
+                kind: PatternKind::Constructor(name, Some(Box::new(payload))),
+                span: e.span(),
+            })
+            .or(atom)
+    });
+
+    pattern.validate(|pattern: Pattern, _, emitter| {
+        for name in duplicates(pattern.variables()) {
+            emitter.emit(Rich::custom(
+                name.span,
+                format!("`{}` is bound more than once in this pattern", name.name),
+            ));
         }
-        .then_ignore(just(TokenKind::Colon))
-        .then(expr.clone());
+        pattern
+    })
+}
+
+pub fn expression_parser<'s, E: ParserExtra<'s, ParserInput<'s>>>(
+) -> impl Parser<'s, ParserInput<'s>, Expr, extra::Err<Rich<'s, TokenKind<'s>, Span>>> + Clone {
+    recursive(|expr| {
+        let literal = literal_parser().map(ExprKind::Literal);
+
+        let identifier = select! {
+            TokenKind::Ident(s) = e => Identifier { name: s.to_string(), span: e.span() },
+        };
+
+        let ident = identifier.map(ExprKind::Identifier);
+
+        // `(a)` is just `a`, with more elements it's a tuple
+        let grouping = expr
+            .clone()
+            .separated_by(just(TokenKind::Comma))
+            .at_least(1)
+            .collect::<Vec<_>>()
+            .delimited_by(
+                just(TokenKind::LeftParenthesis),
+                just(TokenKind::RightParenthesis),
+            )
+            .map(|mut elements: Vec<Expr>| match elements.len() {
+                1 => elements.remove(0).kind,
+                _ => ExprKind::Tuple(elements),
+            });
+
+        let struct_elem = identifier
+            .then_ignore(just(TokenKind::Colon))
+            .then(expr.clone());
 
-        let r#struct = struct_elem
+        let struct_fields = struct_elem
             .separated_by(just(TokenKind::Comma))
-            .collect::<HashMap<_, _>>()
-            .map(Expr::Product)
+            .collect::<Vec<_>>()
+            .validate(|fields: Vec<(Identifier, Expr)>, _, emitter| {
+                for name in duplicates(fields.iter().map(|(name, _)| name)) {
+                    emitter.emit(Rich::custom(
+                        name.span,
+                        format!("field `{}` is defined more than once", name.name),
+                    ));
+                }
+                fields
+            });
+
+        // `{ a: 1 }`, or `{ a: 1 | record }` to extend `record`
+        let r#struct = struct_fields
+            .clone()
+            .then(
+                just(TokenKind::Symbol("|"))
+                    .ignore_then(expr.clone())
+                    .or_not(),
+            )
+            .map(|(fields, record)| match record {
+                Some(record) => ExprKind::Extension(Extension {
+                    fields,
+                    record: Box::new(record),
+                }),
+                None => ExprKind::Product(fields),
+            })
             .delimited_by(just(TokenKind::LeftCurly), just(TokenKind::RightCurly))
             .labelled("struct");
 
-        let lambda = select! {
-            TokenKind::Ident(s) => Identifier { name: s.to_string() },
-        }
-        .then_ignore(just(TokenKind::Arrow))
-        .then(expr)
-        .map(|(from, to)| Lambda {
-            from,
-            to: Box::new(to),
+        // `{ record | a: 1 }`
+        let update = expr
+            .clone()
+            .then_ignore(just(TokenKind::Symbol("|")))
+            .then(struct_fields)
+            .map(|(record, fields)| {
+                ExprKind::Update(Update {
+                    record: Box::new(record),
+                    fields,
+                })
+            })
+            .delimited_by(just(TokenKind::LeftCurly), just(TokenKind::RightCurly))
+            .labelled("struct");
+
+        // `a b => body` is sugar for `a => b => body`
+        let lambda = pattern_atom_parser(pattern_parser::<E>())
+            .repeated()
+            .at_least(1)
+            .collect::<Vec<_>>()
+            .validate(|parameters: Vec<Pattern>, _, emitter| {
+                for name in duplicates(parameters.iter().flat_map(Pattern::variables)) {
+                    emitter.emit(Rich::custom(
+                        name.span,
+                        format!("`{}` is bound more than once in the parameters", name.name),
+                    ));
+                }
+                parameters
+            })
+            .then_ignore(just(TokenKind::Arrow))
+            .then(expr.clone())
+            .map(|(mut parameters, body)| {
+                let from = parameters.remove(0);
+                let to = parameters.into_iter().rev().fold(body, |body, from| Expr {
+                    span: from.span.union(body.span),
+                    kind: ExprKind::Lambda(Lambda {
+                        from,
+                        to: Box::new(body),
+                        curried: true,
+                    }),
+                });
+                ExprKind::Lambda(Lambda {
+                    from,
+                    to: Box::new(to),
+                    curried: false,
+                })
+            })
+            .labelled("lambda");
+
+        let conditional = just(TokenKind::If)
+            .ignore_then(expr.clone())
+            .then_ignore(just(TokenKind::Then))
+            .then(expr.clone())
+            .then_ignore(just(TokenKind::Else))
+            .then(expr.clone())
+            .map(|((condition, then_branch), else_branch)| {
+                ExprKind::If(If {
+                    condition: Box::new(condition),
+                    then_branch: Box::new(then_branch),
+                    else_branch: Box::new(else_branch),
+                })
+            })
+            .labelled("conditional");
+
+        let binding = identifier
+            .then(
+                just(TokenKind::Colon)
+                    .ignore_then(annotation_parser::<E>())
+                    .or_not(),
+            )
+            .then_ignore(just(TokenKind::Bind))
+            .then(expr.clone())
+            .map(|((lhs, annotation), rhs)| Binding {
+                lhs,
+                annotation,
+                rhs,
+            });
+
+        let r#let = just(TokenKind::Let)
+            .ignore_then(
+                binding
+                    .separated_by(just(TokenKind::Semicolon))
+                    .allow_trailing()
+                    .at_least(1)
+                    .collect::<Vec<_>>()
+                    .validate(|bindings: Vec<Binding>, _, emitter| {
+                        for name in duplicates(bindings.iter().map(|b| &b.lhs)) {
+                            emitter.emit(Rich::custom(
+                                name.span,
+                                format!("`{}` is defined more than once", name.name),
+                            ));
+                        }
+                        bindings
+                    }),
+            )
+            .then_ignore(just(TokenKind::In))
+            .then(expr.clone())
+            .map(|(bindings, body)| {
+                ExprKind::Let(Let {
+                    bindings,
+                    body: Box::new(body),
+                })
+            })
+            .labelled("let");
+
+        // The lexer splits the string around the embedded expressions
+        let head = select! { TokenKind::StringHead(s) => s };
+        let middle = select! { TokenKind::StringMiddle(s) => s };
+        let tail = select! { TokenKind::StringTail(s) => s };
+        let interpolation = head
+            .then(expr.clone())
+            .then(middle.then(expr.clone()).repeated().collect::<Vec<_>>())
+            .then(tail)
+            .map(|(((head, first), rest), tail)| {
+                let mut parts = vec![StringPart::Text(head), StringPart::Expr(first)];
+                for (text, expr) in rest {
+                    parts.extend([StringPart::Text(text), StringPart::Expr(expr)]);
+                }
+                parts.push(StringPart::Text(tail));
+                parts.retain(|part| !matches!(part, StringPart::Text(s) if s.is_empty()));
+                ExprKind::Interpolation(parts)
+            })
+            .labelled("string");
+
+        let arm = pattern_parser::<E>()
+            .then_ignore(just(TokenKind::Arrow))
+            .then(expr.clone())
+            .map(|(pattern, body)| MatchArm { pattern, body });
+
+        // At least one arm, otherwise `match x {}` would look like `x` applied
+        // to an empty record
+        let r#match = just(TokenKind::Match)
+            .ignore_then(expr)
+            .then(
+                arm.separated_by(just(TokenKind::Comma))
+                    .allow_trailing()
+                    .at_least(1)
+                    .collect::<Vec<_>>()
+                    .delimited_by(just(TokenKind::LeftCurly), just(TokenKind::RightCurly)),
+            )
+            .map(|(scrutinee, arms)| {
+                ExprKind::Match(Match {
+                    scrutinee: Box::new(scrutinee),
+                    arms,
+                })
+            })
+            .labelled("match");
+
+        // Patterns look like literals, records and groupings, so lambdas go
+        // first
+        let atom = choice((
+            lambda,
+            literal,
+            interpolation,
+            update,
+            r#struct,
+            conditional,
+            r#let,
+            r#match,
+            ident,
+            grouping,
+        ))
+        .map_with(|kind, e| Expr {
+            kind,
+            span: e.span(),
         })
-        .map(Expr::Lambda)
-        .labelled("lambda");
+        .labelled("expression");
 
-        let atom = choice((literal, r#struct, lambda, ident, grouping)).labelled("atom");
+        // Field access binds tighter than application, so `f r.a` is `f (r.a)`
+        let field = just(TokenKind::Dot).ignore_then(identifier);
+        let atom = atom.pratt((postfix(0, field, |record: Expr, field, span| Expr {
+            kind: ExprKind::FieldAccess(FieldAccess {
+                record: Box::new(record),
+                field,
+            }),
+            span,
+        }),));
 
         // Left associative application
-        let application = atom.clone().foldl(atom.repeated(), |op, o| {
-            Expr::FunctionCall(FunctionCall {
+        let application = atom.clone().foldl(atom.repeated(), |op, o| Expr {
+            span: op.span.union(o.span),
+            kind: ExprKind::FunctionCall(FunctionCall {
                 function: Box::new(op),
                 argument: Box::new(o),
-            })
+            }),
         });
 
+        let symbol_expr = |name: &str, span: Span| Expr {
+            kind: ExprKind::Identifier(Identifier {
+                name: name.to_string(),
+                span,
+            }),
+            span,
+        };
+
+        // Every other symbol binds tighter than these. `pratt` moves on to
+        // the next operator when one binds too loosely, so `any_symbol` must
+        // not match them.
+        const OPERATORS: [&str; 9] = ["$", "==", "!=", "<", "<=", ">", ">=", "+", "-"];
+
+        // `|` isn't an operator, it separates a record from its fields
         let any_symbol = select! {
-            TokenKind::Symbol(s) => Identifier { name: s.to_string() },
-        }
-        .map(Expr::Identifier);
+            TokenKind::Symbol(s) = e if !OPERATORS.contains(&s) && s != "|" => symbol_expr(s, e.span()),
+        };
 
         let mk_symbol = |c| {
             select! {
-                TokenKind::Symbol(s) if c == s => Identifier { name: s.to_string() },
+                TokenKind::Symbol(s) = e if c == s => symbol_expr(s, e.span()),
             }
-            .map(Expr::Identifier)
         };
 
-        let infix_fold = |left: Expr, op: Expr, right: Expr| {
+        let infix_fold = |left: Expr, op: Expr, right: Expr, span: Span| {
             trace!("Creating infix");
-            let first_op = Expr::FunctionCall(FunctionCall {
-                function: Box::new(op),
-                argument: Box::new(left),
-            });
-            Expr::FunctionCall(FunctionCall {
-                function: Box::new(first_op),
-                argument: Box::new(right),
-            })
+            let first_op = Expr {
+                span: left.span.union(op.span),
+                kind: ExprKind::FunctionCall(FunctionCall {
+                    function: Box::new(op),
+                    argument: Box::new(left),
+                }),
+            };
+            Expr {
+                kind: ExprKind::FunctionCall(FunctionCall {
+                    function: Box::new(first_op),
+                    argument: Box::new(right),
+                }),
+                span,
+            }
         };
 
-        application.pratt((
-            infix(left(2), any_symbol, infix_fold),
-            infix(left(1), mk_symbol("+"), infix_fold),
-            infix(left(1), mk_symbol("-"), infix_fold),
+        let comparison = choice((
+            mk_symbol("=="),
+            mk_symbol("!="),
+            mk_symbol("<"),
+            mk_symbol("<="),
+            mk_symbol(">"),
+            mk_symbol(">="),
+        ));
+
+        let operators = application.pratt((
             infix(left(0), mk_symbol("$"), infix_fold),
+            infix(left(1), comparison, infix_fold),
+            infix(left(2), mk_symbol("+"), infix_fold),
+            infix(left(2), mk_symbol("-"), infix_fold),
+            infix(left(3), any_symbol, infix_fold),
+        ));
+
+        // `e : T` binds looser than every operator
+        operators
+            .then(
+                just(TokenKind::Colon)
+                    .ignore_then(type_parser::<E>())
+                    .or_not(),
+            )
+            .map_with(|(expr, ty), e| match ty {
+                Some(ty) => Expr {
+                    kind: ExprKind::Ascription(Ascription {
+                        expr: Box::new(expr),
+                        ty: Box::new(ty),
+                    }),
+                    span: e.span(),
+                },
+                None => expr,
+            })
+    })
+}
+
+/// Type expressions, where application binds tighter than `->`, which is
+/// right associative.
+pub fn type_parser<'s, E: ParserExtra<'s, ParserInput<'s>>>(
+) -> impl Parser<'s, ParserInput<'s>, TypeExpr, extra::Err<Rich<'s, TokenKind<'s>, Span>>> + Clone {
+    recursive(|ty| {
+        let variable = select! {
+            TokenKind::Ident(s) = e if !is_constructor(s) => Identifier { name: s.to_string(), span: e.span() },
+        };
+
+        let constructor = select! {
+            TokenKind::Ident(s) = e if is_constructor(s) => Identifier { name: s.to_string(), span: e.span() },
+        };
+
+        let record = variable
+            .then_ignore(just(TokenKind::Colon))
+            .then(ty.clone())
+            .separated_by(just(TokenKind::Comma))
+            .collect::<Vec<_>>()
+            .validate(|fields: Vec<(Identifier, TypeExpr)>, _, emitter| {
+                for name in duplicates(fields.iter().map(|(name, _)| name)) {
+                    emitter.emit(Rich::custom(
+                        name.span,
+                        format!("field `{}` is defined more than once", name.name),
+                    ));
+                }
+                fields
+            })
+            .then(just(TokenKind::Symbol("|")).ignore_then(variable).or_not())
+            .delimited_by(just(TokenKind::LeftCurly), just(TokenKind::RightCurly))
+            .map(|(fields, rest)| TypeExprKind::Record { fields, rest });
+
+        // `(a)` is just `a`, with more elements it's a tuple
+        let grouping = ty
+            .clone()
+            .separated_by(just(TokenKind::Comma))
+            .at_least(1)
+            .collect::<Vec<_>>()
+            .delimited_by(
+                just(TokenKind::LeftParenthesis),
+                just(TokenKind::RightParenthesis),
+            )
+            .map(|mut elements: Vec<TypeExpr>| match elements.len() {
+                1 => elements.remove(0).kind,
+                _ => TypeExprKind::Tuple(elements),
+            });
+
+        let atom = choice((
+            variable.map(TypeExprKind::Variable),
+            constructor.map(|name| TypeExprKind::Named(name, Vec::new())),
+            record,
+            grouping,
         ))
+        .map_with(|kind, e| TypeExpr {
+            kind,
+            span: e.span(),
+        })
+        .labelled("type");
+
+        let application = constructor
+            .then(atom.clone().repeated().at_least(1).collect::<Vec<_>>())
+            .map_with(|(name, arguments), e| TypeExpr {
+                kind: TypeExprKind::Named(name, arguments),
+                span: e.span(),
+            })
+            .or(atom);
+
+        application
+            .then(just(TokenKind::Symbol("->")).ignore_then(ty).or_not())
+            .map_with(|(from, to), e| match to {
+                Some(to) => TypeExpr {
+                    kind: TypeExprKind::Function(Box::new(from), Box::new(to)),
+                    span: e.span(),
+                },
+                None => from,
+            })
     })
 }
 
-pub fn ast_parser<'s, E: ParserExtra<'s, &'s [TokenKind<'s>]>>(
-) -> impl Parser<'s, &'s [TokenKind<'s>], Ast, extra::Err<Rich<'s, TokenKind<'s>>>> {
+/// The annotation of a binding, a type that may start with the classes of
+/// its variables, as `Eq a => a -> a -> Bool` or `(Eq a, Add b) => ...`.
+pub fn annotation_parser<'s, E: ParserExtra<'s, ParserInput<'s>>>(
+) -> impl Parser<'s, ParserInput<'s>, TypeExpr, extra::Err<Rich<'s, TokenKind<'s>, Span>>> + Clone {
+    let class = select! {
+        TokenKind::Ident(s) = e if is_constructor(s) => Identifier { name: s.to_string(), span: e.span() },
+    };
+
+    let variable = select! {
+        TokenKind::Ident(s) = e if !is_constructor(s) => Identifier { name: s.to_string(), span: e.span() },
+    };
+
+    let constraint = class
+        .then(variable)
+        .map(|(class, variable)| Constraint { class, variable });
+
+    let constraints = choice((
+        constraint.map(|constraint| vec![constraint]),
+        constraint
+            .separated_by(just(TokenKind::Comma))
+            .at_least(1)
+            .collect::<Vec<_>>()
+            .delimited_by(
+                just(TokenKind::LeftParenthesis),
+                just(TokenKind::RightParenthesis),
+            ),
+    ))
+    .then_ignore(just(TokenKind::Arrow));
+
+    // Without `=>`, what looked like constraints is a type, as `Option a`
+    constraints
+        .then(type_parser::<E>())
+        .map_with(|(constraints, ty), e| TypeExpr {
+            kind: TypeExprKind::Constrained {
+                constraints,
+                ty: Box::new(ty),
+            },
+            span: e.span(),
+        })
+        .or(type_parser::<E>())
+}
+
+/// `type Name parameters = Variant payload | ...`
+pub fn type_decl_parser<'s, E: ParserExtra<'s, ParserInput<'s>>>(
+) -> impl Parser<'s, ParserInput<'s>, TypeDecl, extra::Err<Rich<'s, TokenKind<'s>, Span>>> + Clone {
+    let constructor = select! {
+        TokenKind::Ident(s) = e if is_constructor(s) => Identifier { name: s.to_string(), span: e.span() },
+    }
+    .labelled("constructor");
+
+    let variable = select! {
+        TokenKind::Ident(s) = e if !is_constructor(s) => Identifier { name: s.to_string(), span: e.span() },
+    }
+    .labelled("type variable");
+
+    let variant = constructor
+        .then(type_parser::<E>().or_not())
+        .map(|(name, payload)| Variant { name, payload });
+
+    just(TokenKind::Type)
+        .ignore_then(constructor)
+        .then(variable.repeated().collect::<Vec<_>>())
+        .then_ignore(just(TokenKind::Bind))
+        .then(
+            variant
+                .separated_by(just(TokenKind::Symbol("|")))
+                .at_least(1)
+                .collect::<Vec<_>>(),
+        )
+        .validate(|((name, parameters), variants), _, emitter| {
+            for parameter in duplicates(&parameters) {
+                emitter.emit(Rich::custom(
+                    parameter.span,
+                    format!(
+                        "type variable `{}` is declared more than once",
+                        parameter.name
+                    ),
+                ));
+            }
+            TypeDecl {
+                name,
+                parameters,
+                variants,
+            }
+        })
+        .labelled("type declaration")
+}
+
+pub fn ast_parser<'s, E: ParserExtra<'s, ParserInput<'s>>>(
+) -> impl Parser<'s, ParserInput<'s>, Ast, extra::Err<Rich<'s, TokenKind<'s>, Span>>> {
     let ep = expression_parser::<E>();
 
     let binding = select! {
-        TokenKind::Ident(s) => Identifier { name: s.to_string() },
+        TokenKind::Ident(s) = e => Identifier { name: s.to_string(), span: e.span() },
     }
+    .then(
+        just(TokenKind::Colon)
+            .ignore_then(annotation_parser::<E>())
+            .or_not(),
+    )
     .then_ignore(just(TokenKind::Bind))
     .then(ep.clone())
-    .map(|(lhs, rhs)| Ast::Binding { lhs, rhs });
+    .map(|((lhs, annotation), rhs)| {
+        Ast::Binding(Binding {
+            lhs,
+            annotation,
+            rhs,
+        })
+    });
 
-    choice((binding, ep.map(Ast::Expr)))
+    choice((
+        type_decl_parser::<E>().map(Ast::TypeDecl),
+        binding,
+        ep.map(Ast::Expr),
+    ))
+}
+
+/// Statements are separated by `;` or by a [`TokenKind::Newline`], and only
+/// the last one may be an expression.
+pub fn program_parser<'s, E: ParserExtra<'s, ParserInput<'s>>>(
+) -> impl Parser<'s, ParserInput<'s>, Program, extra::Err<Rich<'s, TokenKind<'s>, Span>>> {
+    // Doc comments are only read by documentation tools
+    let separator = select! {
+        TokenKind::Semicolon => (),
+        TokenKind::Newline => (),
+        TokenKind::DocComment(_) => (),
+    }
+    .repeated()
+    .at_least(1);
+
+    ast_parser::<E>()
+        .separated_by(separator)
+        .allow_leading()
+        .allow_trailing()
+        .collect::<Vec<_>>()
+        .validate(|statements, _, emitter| {
+            let (program, errors) = Program::from_statements(statements);
+            for error in errors {
+                emitter.emit(error);
+            }
+            program
+        })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::lexer::SpanContext;
     use chumsky::error;
     use insta::assert_debug_snapshot;
     use rstest::rstest;
     use tracing_test::traced_test;
-    type TestExtra = extra::Err<error::Cheap>;
+    type TestExtra = extra::Err<error::Cheap<Span>>;
+
+    /// Gives every token a one-byte span, as if they had been lexed from a
+    /// source without whitespace.
+    fn spanned<'src>(tokens: &[TokenKind<'src>]) -> Vec<Token<'src>> {
+        tokens
+            .iter()
+            .enumerate()
+            .map(|(i, t)| (t.clone(), SpanContext::default().span(i..i + 1)))
+            .collect()
+    }
+
+    fn eoi(tokens: &[TokenKind]) -> Span {
+        SpanContext::default().span(tokens.len()..tokens.len())
+    }
 
     #[rstest]
     #[traced_test]
@@ -239,29 +1101,327 @@ mod tests {
                 TokenKind::Ident("b"),
                 TokenKind::RightCurly
             ][..]),
+            ("field_access", &[
+                TokenKind::Ident("f"),
+                TokenKind::Ident("r"),
+                TokenKind::Dot,
+                TokenKind::Ident("a"),
+                TokenKind::Dot,
+                TokenKind::Ident("b"),
+            ][..]),
+            ("conditional", &[
+                TokenKind::If,
+                TokenKind::Ident("a"),
+                TokenKind::Symbol("=="),
+                TokenKind::Number(1.into()),
+                TokenKind::Then,
+                TokenKind::Ident("b"),
+                TokenKind::Else,
+                TokenKind::Ident("c"),
+                TokenKind::Symbol("+"),
+                TokenKind::Number(1.into()),
+            ][..]),
+            ("let", &[
+                TokenKind::Let,
+                TokenKind::Ident("a"),
+                TokenKind::Bind,
+                TokenKind::Number(1.into()),
+                TokenKind::Semicolon,
+                TokenKind::Ident("b"),
+                TokenKind::Bind,
+                TokenKind::Ident("a"),
+                TokenKind::In,
+                TokenKind::Ident("f"),
+                TokenKind::Ident("b"),
+            ][..]),
+            ("match", &[
+                TokenKind::Match,
+                TokenKind::Ident("f"),
+                TokenKind::Ident("x"),
+                TokenKind::LeftCurly,
+                TokenKind::Ident("Some"),
+                TokenKind::Ident("y"),
+                TokenKind::Arrow,
+                TokenKind::Ident("y"),
+                TokenKind::Comma,
+                TokenKind::Underscore,
+                TokenKind::Arrow,
+                TokenKind::Number(0.into()),
+                TokenKind::Comma,
+                TokenKind::RightCurly,
+            ][..]),
+            ("tuple", &[
+                TokenKind::LeftParenthesis,
+                TokenKind::Ident("a"),
+                TokenKind::Comma,
+                TokenKind::LeftParenthesis,
+                TokenKind::Ident("b"),
+                TokenKind::RightParenthesis,
+                TokenKind::RightParenthesis,
+            ][..]),
+            ("lambda_destructuring", &[
+                TokenKind::LeftCurly,
+                TokenKind::Ident("a"),
+                TokenKind::Comma,
+                TokenKind::Ident("b"),
+                TokenKind::Colon,
+                TokenKind::LeftParenthesis,
+                TokenKind::Underscore,
+                TokenKind::Comma,
+                TokenKind::Ident("c"),
+                TokenKind::RightParenthesis,
+                TokenKind::RightCurly,
+                TokenKind::Arrow,
+                TokenKind::Ident("c"),
+            ][..]),
+            ("lambda_curried", &[
+                TokenKind::Ident("a"),
+                TokenKind::LeftParenthesis,
+                TokenKind::Ident("Some"),
+                TokenKind::Ident("b"),
+                TokenKind::RightParenthesis,
+                TokenKind::Underscore,
+                TokenKind::Arrow,
+                TokenKind::Ident("c"),
+                TokenKind::Arrow,
+                TokenKind::Ident("a"),
+            ][..]),
+            ("lambda_duplicate", &[
+                TokenKind::Ident("a"),
+                TokenKind::Ident("a"),
+                TokenKind::Arrow,
+                TokenKind::Ident("a"),
+            ][..]),
+            ("struct_duplicate", &[
+                TokenKind::LeftCurly,
+                TokenKind::Ident("a"),
+                TokenKind::Colon,
+                TokenKind::Number(1.into()),
+                TokenKind::Comma,
+                TokenKind::Ident("a"),
+                TokenKind::Colon,
+                TokenKind::Number(2.into()),
+                TokenKind::RightCurly
+            ][..]),
+            ("record_extension", &[
+                TokenKind::LeftCurly,
+                TokenKind::Ident("c"),
+                TokenKind::Colon,
+                TokenKind::Number(3.into()),
+                TokenKind::Symbol("|"),
+                TokenKind::Ident("r"),
+                TokenKind::RightCurly
+            ][..]),
+            ("record_update", &[
+                TokenKind::LeftCurly,
+                TokenKind::Ident("r"),
+                TokenKind::Dot,
+                TokenKind::Ident("s"),
+                TokenKind::Symbol("|"),
+                TokenKind::Ident("a"),
+                TokenKind::Colon,
+                TokenKind::Ident("f"),
+                TokenKind::Number(1.into()),
+                TokenKind::RightCurly
+            ][..]),
+            ("ascription", &[
+                TokenKind::Ident("a"),
+                TokenKind::Symbol("+"),
+                TokenKind::Number(1.into()),
+                TokenKind::Colon,
+                TokenKind::Ident("Option"),
+                TokenKind::Ident("Int"),
+            ][..]),
+            ("interpolation", &[
+                TokenKind::StringHead("a ".into()),
+                TokenKind::Ident("b"),
+                TokenKind::StringMiddle(String::new()),
+                TokenKind::Ident("f"),
+                TokenKind::Number(1.into()),
+                TokenKind::StringTail("!".into()),
+            ][..]),
         )]
         input: (&str, &[TokenKind<'src>]),
     ) {
+        let tokens = spanned(input.1);
         let p = expression_parser::<TestExtra>();
 
-        assert_debug_snapshot!(input.0, p.parse(input.1));
+        assert_debug_snapshot!(input.0, p.parse(tokens.as_slice().spanned(eoi(input.1))));
     }
 
     #[rstest]
     #[traced_test]
     fn test_ast<'src>(
         #[values(
-            ("empty", &[][..]),
+            ("ast_empty", &[][..]),
             ("assign", &[
                 TokenKind::Ident("a"),
                 TokenKind::Bind,
                 TokenKind::Ident("b"),
             ][..]),
+            ("assign_constrained", &[
+                TokenKind::Ident("f"),
+                TokenKind::Colon,
+                TokenKind::LeftParenthesis,
+                TokenKind::Ident("Eq"),
+                TokenKind::Ident("a"),
+                TokenKind::Comma,
+                TokenKind::Ident("Add"),
+                TokenKind::Ident("a"),
+                TokenKind::RightParenthesis,
+                TokenKind::Arrow,
+                TokenKind::Ident("a"),
+                TokenKind::Bind,
+                TokenKind::Ident("b"),
+            ][..]),
+            ("assign_annotated", &[
+                TokenKind::Ident("a"),
+                TokenKind::Colon,
+                TokenKind::Ident("a"),
+                TokenKind::Symbol("->"),
+                TokenKind::Ident("a"),
+                TokenKind::Bind,
+                TokenKind::Ident("b"),
+            ][..]),
         )]
         input: (&str, &[TokenKind<'src>]),
     ) {
+        let tokens = spanned(input.1);
         let p = ast_parser::<TestExtra>();
 
-        assert_debug_snapshot!(input.0, p.parse(input.1));
+        assert_debug_snapshot!(input.0, p.parse(tokens.as_slice().spanned(eoi(input.1))));
+    }
+
+    #[rstest]
+    #[traced_test]
+    fn test_pattern<'src>(
+        #[values(
+            ("pattern_nested", &[
+                TokenKind::Ident("Some"),
+                TokenKind::LeftCurly,
+                TokenKind::Ident("a"),
+                TokenKind::Colon,
+                TokenKind::Ident("Ok"),
+                TokenKind::Underscore,
+                TokenKind::Comma,
+                TokenKind::Ident("b"),
+                TokenKind::Comma,
+                TokenKind::Ident("c"),
+                TokenKind::Colon,
+                TokenKind::Number(1.into()),
+                TokenKind::RightCurly,
+            ][..]),
+            ("pattern_duplicate", &[
+                TokenKind::LeftCurly,
+                TokenKind::Ident("a"),
+                TokenKind::Comma,
+                TokenKind::Ident("b"),
+                TokenKind::Colon,
+                TokenKind::Ident("a"),
+                TokenKind::RightCurly,
+            ][..]),
+            ("pattern_annotated", &[
+                TokenKind::LeftParenthesis,
+                TokenKind::Ident("n"),
+                TokenKind::Colon,
+                TokenKind::Ident("Int"),
+                TokenKind::RightParenthesis,
+            ][..]),
+        )]
+        input: (&str, &[TokenKind<'src>]),
+    ) {
+        let tokens = spanned(input.1);
+        let p = pattern_parser::<TestExtra>();
+
+        assert_debug_snapshot!(input.0, p.parse(tokens.as_slice().spanned(eoi(input.1))));
+    }
+
+    #[rstest]
+    #[traced_test]
+    fn test_type_decl<'src>(
+        #[values(
+            ("type_decl_option", &[
+                TokenKind::Type,
+                TokenKind::Ident("Option"),
+                TokenKind::Ident("a"),
+                TokenKind::Bind,
+                TokenKind::Ident("Some"),
+                TokenKind::Ident("a"),
+                TokenKind::Symbol("|"),
+                TokenKind::Ident("None"),
+            ][..]),
+            ("type_decl_payloads", &[
+                TokenKind::Type,
+                TokenKind::Ident("T"),
+                TokenKind::Bind,
+                TokenKind::Ident("A"),
+                TokenKind::Ident("List"),
+                TokenKind::Ident("Int"),
+                TokenKind::Symbol("|"),
+                TokenKind::Ident("B"),
+                TokenKind::LeftCurly,
+                TokenKind::Ident("f"),
+                TokenKind::Colon,
+                TokenKind::Ident("a"),
+                TokenKind::Symbol("->"),
+                TokenKind::Ident("b"),
+                TokenKind::Symbol("->"),
+                TokenKind::Ident("a"),
+                TokenKind::RightCurly,
+            ][..]),
+            ("type_decl_lowercase", &[
+                TokenKind::Type,
+                TokenKind::Ident("T"),
+                TokenKind::Bind,
+                TokenKind::Ident("a"),
+            ][..]),
+        )]
+        input: (&str, &[TokenKind<'src>]),
+    ) {
+        let tokens = spanned(input.1);
+        let p = type_decl_parser::<TestExtra>();
+
+        assert_debug_snapshot!(input.0, p.parse(tokens.as_slice().spanned(eoi(input.1))));
+    }
+
+    #[rstest]
+    #[traced_test]
+    fn test_program<'src>(
+        #[values(
+            ("program_empty", &[][..]),
+            ("program_bindings", &[
+                TokenKind::Ident("a"),
+                TokenKind::Bind,
+                TokenKind::Number(1.into()),
+                TokenKind::Semicolon,
+                TokenKind::Ident("b"),
+                TokenKind::Bind,
+                TokenKind::Ident("a"),
+                TokenKind::Newline,
+            ][..]),
+            ("program_body", &[
+                TokenKind::Newline,
+                TokenKind::Ident("a"),
+                TokenKind::Bind,
+                TokenKind::Number(1.into()),
+                TokenKind::Newline,
+                TokenKind::Newline,
+                TokenKind::Ident("f"),
+                TokenKind::Ident("a"),
+            ][..]),
+            ("program_expr_not_last", &[
+                TokenKind::Ident("a"),
+                TokenKind::Semicolon,
+                TokenKind::Ident("b"),
+                TokenKind::Bind,
+                TokenKind::Number(1.into()),
+            ][..]),
+        )]
+        input: (&str, &[TokenKind<'src>]),
+    ) {
+        let tokens = spanned(input.1);
+        let p = program_parser::<TestExtra>();
+
+        assert_debug_snapshot!(input.0, p.parse(tokens.as_slice().spanned(eoi(input.1))));
     }
 }
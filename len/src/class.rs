@@ -0,0 +1,121 @@
+use crate::ty::Primitive;
+use std::fmt;
+
+/// A set of types that share an overloaded operator, like `Add` for `+`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Class {
+    /// `+`
+    Add,
+    /// `-` and `*`, and the type of integer literals
+    Num,
+    /// `==` and `!=`
+    Eq,
+    /// `<`, `<=`, `>` and `>=`
+    Ord,
+    /// `${}` in strings
+    Show,
+}
+
+impl Class {
+    pub fn name(self) -> &'static str {
+        match self {
+            Class::Add => "Add",
+            Class::Num => "Num",
+            Class::Eq => "Eq",
+            Class::Ord => "Ord",
+            Class::Show => "Show",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "Add" => Some(Class::Add),
+            "Num" => Some(Class::Num),
+            "Eq" => Some(Class::Eq),
+            "Ord" => Some(Class::Ord),
+            "Show" => Some(Class::Show),
+            _ => None,
+        }
+    }
+
+    /// The classes that every instance of this one is also in.
+    pub fn superclasses(self) -> &'static [Class] {
+        match self {
+            Class::Num => &[Class::Add],
+            _ => &[],
+        }
+    }
+
+    /// The types of the class, in words, for error messages.
+    pub fn members(self) -> &'static str {
+        match self {
+            Class::Add => "Int, Rational, String and List a",
+            Class::Num => "Int and Rational",
+            Class::Eq => "the types without functions",
+            Class::Ord => "Int, Rational and String",
+            Class::Show => "the types without functions",
+        }
+    }
+
+    /// The instance of the class for the types built by `head`, if they are
+    /// in the class.
+    ///
+    /// The type checker asks with the type of the operands, and the
+    /// evaluator with the values that they turn out to be. Values have the
+    /// head of their type, so both get the same instance.
+    pub fn instance(self, head: Head<'_>) -> Option<Instance> {
+        match (self, head) {
+            (Class::Add | Class::Num, Head::Primitive(Primitive::Int | Primitive::Rational)) => {
+                Some(Instance::Arithmetic)
+            }
+            (Class::Add, Head::Primitive(Primitive::String)) => Some(Instance::StringConcatenation),
+            (Class::Add, Head::Sum(LIST)) => Some(Instance::ListConcatenation),
+            (Class::Eq, Head::Function) => None,
+            (Class::Eq, _) => Some(Instance::StructuralEquality),
+            (Class::Show, Head::Function) => None,
+            (Class::Show, _) => Some(Instance::StructuralDisplay),
+            (
+                Class::Ord,
+                Head::Primitive(Primitive::Int | Primitive::Rational | Primitive::String),
+            ) => Some(Instance::Comparison),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Class {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// The built-in list type, `type List a = Nil | Cons (a, List a)`.
+pub const LIST: &str = "List";
+
+/// The outermost constructor of a type, which picks the instance of a class.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Head<'a> {
+    Primitive(Primitive),
+    Function,
+    Tuple,
+    Record,
+    /// A sum type, by name.
+    Sum(&'a str),
+}
+
+/// How the operators of a class work on the types of one of its instances.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instance {
+    /// Integers stay integers, and are promoted to rationals when the other
+    /// operand is one.
+    Arithmetic,
+    StringConcatenation,
+    /// Appends the elements of the right list to the left one.
+    ListConcatenation,
+    /// Compares values part by part, so the parts must be in `Eq` too.
+    StructuralEquality,
+    /// Numbers by value, strings by their characters.
+    Comparison,
+    /// Writes values part by part, so the parts must be in `Show` too.
+    StructuralDisplay,
+}
@@ -1,48 +1,68 @@
 use crate::{
     ast,
-    eval::{Object, Scope},
-    lexer::{lexer, Token},
+    cst::Cst,
+    diagnostic::{render, Diagnostic, Style},
+    eval::Scope,
+    infer::{check_program, Env},
+    source::{FileId, Sources},
 };
-use chumsky::{error::Rich, extra, Parser};
-use std::io::{self, Write};
-use tracing::{debug, info};
+use tracing::debug;
 
-pub fn complete(input: &str, scope: Option<Scope>) -> Scope {
-    let tokens = lexer::<extra::Err<Rich<_>>>().parse(input);
-    debug!("tokens={:#?}", tokens);
+/// Lexes and parses a file that has been loaded into `sources`.
+pub fn parse(sources: &Sources, file: FileId) -> Result<ast::Program, Vec<Diagnostic>> {
+    let cst = Cst::parse(sources.get(file).text(), file.context());
+    debug!("cst={:#?}", cst.root);
 
-    let mut scope = match scope {
-        None => Scope::std(),
-        Some(s) => s,
-    };
-
-    if !tokens.has_errors() {
-        let tokens = tokens
-            .into_output()
-            .unwrap()
-            .into_iter()
-            .map(|t| t.kind)
-            .collect::<Vec<_>>();
-
-        let ast = ast::ast_parser::<extra::Err<Rich<_>>>().parse(&tokens);
+    cst.program()
+}
 
-        // let scope = Scope::std();
-        // let mut scope = None;
+/// What the programs run so far have defined, for the next one to build on.
+#[derive(Debug, Clone)]
+pub struct Context {
+    pub scope: Scope,
+    pub env: Env,
+}
 
-        if !ast.has_errors() {
-            let ast = ast.into_output().unwrap();
-            debug!("ast={:#?}", ast);
+impl Default for Context {
+    fn default() -> Self {
+        Self {
+            scope: Scope::std(),
+            env: Env::std(),
+        }
+    }
+}
 
-            let res = crate::eval::eval(ast, scope.clone());
-            if let Some(new_scope) = res {
-                scope = new_scope;
+/// Type checks and runs a file that has been loaded into `sources`, on top of
+/// the bindings of `context`. The value of the program is printed to stdout
+/// and any problem to stderr.
+pub fn complete(sources: &Sources, file: FileId, context: Context, style: Style) -> Context {
+    let diagnostics = match parse(sources, file) {
+        Ok(program) => match check_program(&program, &context.env) {
+            Ok(checked) => {
+                for warning in &checked.warnings {
+                    eprint!("{}", render(&Diagnostic::from(warning), sources, style));
+                }
+                match crate::eval::eval_program(program, context.scope.clone()) {
+                    Ok((scope, value)) => {
+                        if let Some(value) = value {
+                            println!("{value}");
+                        }
+                        return Context {
+                            scope,
+                            env: checked.env,
+                        };
+                    }
+                    Err(err) => vec![Diagnostic::from(&err)],
+                }
             }
-        } else {
-            println!("{:#?}", ast);
-        }
-    } else {
-        println!("{:#?}", tokens);
+            Err(err) => vec![Diagnostic::from(&err)],
+        },
+        Err(diagnostics) => diagnostics,
+    };
+
+    for diagnostic in &diagnostics {
+        eprint!("{}", render(diagnostic, sources, style));
     }
 
-    return scope;
+    context
 }
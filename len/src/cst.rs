@@ -0,0 +1,413 @@
+use crate::ast::{self, ast_parser};
+use crate::diagnostic::Diagnostic;
+use crate::lexer::{lossless_lexer, Lexeme, Span, SpanContext, Token, TokenKind};
+use chumsky::error::Rich;
+use chumsky::input::Input;
+use chumsky::span::Span as _;
+use chumsky::{extra, Parser};
+use std::fmt;
+use tracing::debug;
+
+/// A leaf of the tree, with its exact text in the source.
+#[derive(Clone, PartialEq)]
+pub struct SyntaxToken<'src> {
+    pub kind: Lexeme<'src>,
+    pub text: &'src str,
+    pub span: Span,
+}
+
+impl SyntaxToken<'_> {
+    pub fn is_trivia(&self) -> bool {
+        !matches!(self.kind, Lexeme::Token(_))
+    }
+}
+
+impl fmt::Debug for SyntaxToken<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}@{:?} {:?}", self.kind, self.span, self.text)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+    /// The whole file: statements, the separators between them, and the
+    /// trivia around them.
+    Program,
+    /// The tokens of one binding, type declaration or expression, from its
+    /// first token to its last.
+    Statement,
+    /// `(...)`
+    Parentheses,
+    /// `{...}`, records and the arms of a `match`
+    Braces,
+    /// A string with interpolations, from its [`TokenKind::StringHead`] to
+    /// its [`TokenKind::StringTail`].
+    String,
+}
+
+#[derive(Clone, PartialEq)]
+pub enum SyntaxElement<'src> {
+    Node(SyntaxNode<'src>),
+    Token(SyntaxToken<'src>),
+}
+
+/// A node of the tree, which owns every byte of the source that its span
+/// covers.
+#[derive(Clone, PartialEq)]
+pub struct SyntaxNode<'src> {
+    pub kind: NodeKind,
+    pub span: Span,
+    pub children: Vec<SyntaxElement<'src>>,
+}
+
+impl<'src> SyntaxNode<'src> {
+    fn new(kind: NodeKind, span: Span) -> Self {
+        Self {
+            kind,
+            span,
+            children: Vec::new(),
+        }
+    }
+
+    /// The leaves of the node in source order, trivia included.
+    pub fn tokens(&self) -> Box<dyn Iterator<Item = &SyntaxToken<'src>> + '_> {
+        Box::new(self.children.iter().flat_map(|child| match child {
+            SyntaxElement::Node(node) => node.tokens(),
+            SyntaxElement::Token(token) => Box::new(std::iter::once(token)),
+        }))
+    }
+
+    /// The nodes directly below this one.
+    pub fn nodes(&self) -> impl Iterator<Item = &SyntaxNode<'src>> {
+        self.children.iter().filter_map(|child| match child {
+            SyntaxElement::Node(node) => Some(node),
+            SyntaxElement::Token(_) => None,
+        })
+    }
+
+    /// The tokens of the node for the parser, without the trivia.
+    fn parser_tokens(&self) -> Vec<Token<'src>> {
+        self.tokens()
+            .filter_map(|token| match &token.kind {
+                Lexeme::Token(kind) => Some((kind.clone(), token.span)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Parses the node as a binding, type declaration or expression, which
+    /// is what a [`NodeKind::Statement`] holds.
+    pub fn ast(&self) -> Result<ast::Ast, Vec<Diagnostic>> {
+        match self.parse_ast() {
+            (Some(ast), errors) if errors.is_empty() => Ok(ast),
+            (_, errors) => Err(errors),
+        }
+    }
+
+    /// The statement, even when some errors were recovered from, like the
+    /// bindings of a `let` that are defined twice.
+    fn parse_ast(&self) -> (Option<ast::Ast>, Vec<Diagnostic>) {
+        let tokens = self.parser_tokens();
+        let eoi = self.span.context().span(self.span.end..self.span.end);
+        let (ast, errs) = ast_parser::<extra::Err<Rich<_, _>>>()
+            .parse(tokens.as_slice().spanned(eoi))
+            .into_output_errors();
+        (ast, errs.into_iter().map(Diagnostic::from).collect())
+    }
+
+    fn write_tree(&self, f: &mut fmt::Formatter<'_>, indent: usize) -> fmt::Result {
+        writeln!(f, "{:indent$}{:?}@{:?}", "", self.kind, self.span)?;
+        for child in &self.children {
+            match child {
+                SyntaxElement::Node(node) => node.write_tree(f, indent + 2)?,
+                SyntaxElement::Token(token) => writeln!(f, "{:1$}{token:?}", "", indent + 2)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The source text that the node covers.
+impl fmt::Display for SyntaxNode<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.tokens().try_for_each(|token| f.write_str(token.text))
+    }
+}
+
+/// The tree, one element per line.
+impl fmt::Debug for SyntaxNode<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_tree(f, 0)
+    }
+}
+
+/// A lossless tree of a source file, for tools like formatters that need the
+/// whitespace and comments as they were written. Printing the root gives the
+/// source back byte for byte, even when it doesn't lex.
+///
+/// The tree only groups the tokens into statements and brackets, the
+/// [`ast::Program`] is read from its statements by [`Cst::program`].
+#[derive(Debug, Clone)]
+pub struct Cst<'src> {
+    pub root: SyntaxNode<'src>,
+    pub errors: Vec<Rich<'src, char, Span>>,
+}
+
+impl<'src> Cst<'src> {
+    pub fn parse(source: &'src str, context: SpanContext) -> Self {
+        let (lexemes, errors) = lossless_lexer()
+            .parse(source.with_context(context))
+            .into_output_errors();
+
+        let mut builder = Builder::new(context.span(0..source.len()));
+        for (kind, span) in lexemes.unwrap_or_default() {
+            builder.push(SyntaxToken {
+                kind,
+                text: &source[span.start..span.end],
+                span,
+            });
+        }
+
+        Self {
+            root: builder.finish(),
+            errors,
+        }
+    }
+
+    pub fn statements(&self) -> impl Iterator<Item = &SyntaxNode<'src>> {
+        self.root
+            .nodes()
+            .filter(|node| node.kind == NodeKind::Statement)
+    }
+
+    /// Parses the program one statement at a time, so that every statement
+    /// that doesn't parse is reported. The errors of the lexer come first, as
+    /// the parser can't make sense of what they left out.
+    pub fn program(&self) -> Result<ast::Program, Vec<Diagnostic>> {
+        if !self.errors.is_empty() {
+            return Err(self.errors.iter().cloned().map(Diagnostic::from).collect());
+        }
+
+        let mut statements = Vec::new();
+        let mut diagnostics = Vec::new();
+        let mut complete = true;
+        for statement in self.statements() {
+            let (ast, errs) = statement.parse_ast();
+            complete &= ast.is_some();
+            statements.extend(ast);
+            diagnostics.extend(errs);
+        }
+
+        // Without the statements that don't parse, an expression could look
+        // like the last one when it isn't
+        if complete {
+            let (program, errs) = ast::Program::from_statements(statements);
+            debug!("program={:#?}", program);
+            diagnostics.extend(errs.into_iter().map(Diagnostic::from));
+            if diagnostics.is_empty() {
+                return Ok(program);
+            }
+        }
+        Err(diagnostics)
+    }
+}
+
+/// Groups the leaves into nodes as they come.
+///
+/// Nodes start and end with a token of the language, the trivia around them
+/// goes to their parent. Brackets that don't match are left as plain tokens.
+struct Builder<'src> {
+    /// The open nodes, the program first.
+    stack: Vec<SyntaxNode<'src>>,
+    /// Trivia that goes to whichever node the next token ends up in.
+    trivia: Vec<SyntaxToken<'src>>,
+    /// The `let`s of the current statement that are waiting for their `in`,
+    /// as the `;` between their bindings doesn't end the statement.
+    lets: usize,
+}
+
+impl<'src> Builder<'src> {
+    fn new(span: Span) -> Self {
+        Self {
+            stack: vec![SyntaxNode::new(NodeKind::Program, span)],
+            trivia: Vec::new(),
+            lets: 0,
+        }
+    }
+
+    fn top(&mut self) -> &mut SyntaxNode<'src> {
+        self.stack.last_mut().expect("the program is never closed")
+    }
+
+    fn at_statement_level(&self) -> bool {
+        self.stack.len() <= 2
+    }
+
+    fn push(&mut self, token: SyntaxToken<'src>) {
+        let Lexeme::Token(kind) = &token.kind else {
+            self.trivia.push(token);
+            return;
+        };
+
+        match kind {
+            TokenKind::Newline | TokenKind::DocComment(_) if self.at_statement_level() => {
+                self.separator(token)
+            }
+            TokenKind::Semicolon if self.at_statement_level() && self.lets == 0 => {
+                self.separator(token)
+            }
+            TokenKind::LeftParenthesis => self.open(NodeKind::Parentheses, token),
+            TokenKind::LeftCurly => self.open(NodeKind::Braces, token),
+            TokenKind::StringHead(_) => self.open(NodeKind::String, token),
+            TokenKind::RightParenthesis => self.close(NodeKind::Parentheses, token),
+            TokenKind::RightCurly => self.close(NodeKind::Braces, token),
+            TokenKind::StringTail(_) => self.close(NodeKind::String, token),
+            _ => {
+                if self.at_statement_level() {
+                    match kind {
+                        TokenKind::Let => self.lets += 1,
+                        TokenKind::In => self.lets = self.lets.saturating_sub(1),
+                        _ => {}
+                    }
+                }
+                self.leaf(token)
+            }
+        }
+    }
+
+    fn leaf(&mut self, token: SyntaxToken<'src>) {
+        if self.stack.len() == 1 {
+            self.flush_trivia();
+            self.stack
+                .push(SyntaxNode::new(NodeKind::Statement, token.span));
+        }
+        self.flush_trivia();
+        self.top().children.push(SyntaxElement::Token(token));
+    }
+
+    fn separator(&mut self, token: SyntaxToken<'src>) {
+        if self.stack.len() == 2 {
+            self.pop();
+        }
+        self.lets = 0;
+        self.flush_trivia();
+        self.top().children.push(SyntaxElement::Token(token));
+    }
+
+    fn open(&mut self, kind: NodeKind, token: SyntaxToken<'src>) {
+        let span = token.span;
+        self.leaf(token);
+        let opening = self.top().children.pop().expect("just pushed");
+        let mut node = SyntaxNode::new(kind, span);
+        node.children.push(opening);
+        self.stack.push(node);
+    }
+
+    fn close(&mut self, kind: NodeKind, token: SyntaxToken<'src>) {
+        let matches = self.top().kind == kind;
+        self.leaf(token);
+        if matches {
+            self.pop();
+        }
+    }
+
+    /// Closes the innermost node, which ends at its last token.
+    fn pop(&mut self) {
+        let mut node = self.stack.pop().expect("the program is never closed");
+        if let Some(SyntaxElement::Token(last)) = node.children.last() {
+            node.span.end = last.span.end;
+        } else if let Some(SyntaxElement::Node(last)) = node.children.last() {
+            node.span.end = last.span.end;
+        }
+        self.top().children.push(SyntaxElement::Node(node));
+    }
+
+    fn flush_trivia(&mut self) {
+        let trivia = std::mem::take(&mut self.trivia);
+        let top = self.top();
+        top.children
+            .extend(trivia.into_iter().map(SyntaxElement::Token));
+    }
+
+    fn finish(mut self) -> SyntaxNode<'src> {
+        while self.stack.len() > 1 {
+            self.pop();
+        }
+        self.flush_trivia();
+        self.stack.pop().expect("the program is never closed")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use insta::assert_debug_snapshot;
+    use rstest::rstest;
+
+    fn parse(source: &str) -> Cst<'_> {
+        Cst::parse(source, SpanContext::default())
+    }
+
+    #[rstest]
+    #[case("")]
+    #[case("  \n\t ")]
+    #[case("a = 1; b = a + 2\n\nb * 3\n")]
+    #[case("# comment\nf = x => #[ a #[ nested ]# block ]# x\n  + 1 # trailing")]
+    #[case("## Doc.\n##\ninc = x => x + 1 ## not a doc\n")]
+    #[case("r = {\n  a: (1,\n  2), # first\n  b: \"${ { c: 1 }.c }\"\n}")]
+    #[case("s = \"\"\"\n    a ${x}\n      b\\t\n    \"\"\"")]
+    #[case("match x {\n  Some y => y,\n  _ => 0,\n}")]
+    #[case("unclosed = (1, {\n  a: \"b ${c")]
+    #[case("bad = 0xZZ + \"\\q\" ` ]# @ #[ never closed")]
+    #[case("x = 1 \u{1F600} é\r\n")]
+    fn test_round_trip(#[case] source: &str) {
+        let cst = parse(source);
+
+        assert_eq!(cst.root.to_string(), source);
+        let mut offset = 0;
+        for token in cst.root.tokens() {
+            assert_eq!(token.span.start, offset);
+            assert_eq!(&source[token.span.start..token.span.end], token.text);
+            offset = token.span.end;
+        }
+        assert_eq!(offset, source.len());
+    }
+
+    #[rstest]
+    fn test_tree(
+        #[values(
+            ("tree_statements", "## Doc.\nx = let a = 1; b = 2 in a + b # sum\ny = x; (x,\n  y)"),
+            ("tree_interpolation", "\"a ${ f { b: 1 } } c\""),
+            ("tree_unbalanced", "a = (1 + 2))\nb = { c"),
+            ("tree_unreadable", "a = 1 ` 2 ]# 3"),
+            ("tree_doc_comments", "## Doc.\nr = {\n  ## The first.\n  a: 1 ## trailing\n}"),
+        )]
+        input: (&str, &str),
+    ) {
+        assert_debug_snapshot!(input.0, parse(input.1).root);
+    }
+
+    #[test]
+    fn test_statements() {
+        let cst = parse("x = let a = 1; b = 2 in a + b\n# comment\ntype T = A; T");
+        let statements = cst.statements().collect::<Vec<_>>();
+
+        let texts = statements.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(texts, ["x = let a = 1; b = 2 in a + b", "type T = A", "T"]);
+        assert!(matches!(statements[0].ast(), Ok(ast::Ast::Binding(b)) if b.lhs.name == "x"));
+        assert!(matches!(statements[1].ast(), Ok(ast::Ast::TypeDecl(_))));
+        assert!(matches!(statements[2].ast(), Ok(ast::Ast::Expr(_))));
+    }
+
+    #[test]
+    fn test_program() {
+        let cst = parse("## Doc.\ninc = x => x + 1 # one\ninc 2");
+        let program = cst.program().unwrap();
+
+        assert_eq!(program.bindings.len(), 1);
+        assert!(program.body.is_some());
+        assert!(parse("a = 1 ` 2").program().is_err());
+        assert!(parse("a = (1").program().is_err());
+        assert_eq!(parse("a = 1 +\nb = )\nc").program().unwrap_err().len(), 2);
+    }
+}
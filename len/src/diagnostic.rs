@@ -0,0 +1,515 @@
+use crate::eval::ExprError;
+use crate::exhaustive::PatternWarning;
+use crate::infer::TypeError;
+use crate::lexer::Span;
+use crate::source::{SourceFile, Sources};
+use chumsky::error::{Rich, RichPattern, RichReason};
+use std::fmt::{self, Display, Write};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// A message attached to a span of the source. The primary labels point at
+/// the problem, secondary ones give context.
+#[derive(Debug, Clone)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+    pub primary: bool,
+}
+
+/// A problem found in a program, by any of the stages that run over it.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn error(message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            message: message.into(),
+            labels: Vec::new(),
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn warning(message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            ..Self::error(message)
+        }
+    }
+
+    pub fn with_label(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.labels.push(Label {
+            span,
+            message: message.into(),
+            primary: true,
+        });
+        self
+    }
+
+    pub fn with_secondary_label(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.labels.push(Label {
+            span,
+            message: message.into(),
+            primary: false,
+        });
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+}
+
+/// How a token is called in messages, `token` is its text in the source.
+/// Like `end of input`, it reads after both "unexpected" and "expected".
+fn describe(token: impl Display) -> String {
+    match token.to_string().as_str() {
+        "\n" => String::from("line break"),
+        // Quoted with two backticks, as in Markdown
+        "`" => String::from("`` ` ``"),
+        token => format!("`{token}`"),
+    }
+}
+
+/// Joins the items as "a, b or c".
+fn one_of(items: &[String]) -> String {
+    match items {
+        [] => String::new(),
+        [item] => item.clone(),
+        [init @ .., last] => format!("{} or {last}", init.join(", ")),
+    }
+}
+
+/// Errors of both the lexer, where `T` is `char`, and the parser, where it is
+/// a [`TokenKind`](crate::lexer::TokenKind).
+impl<T: Display> From<Rich<'_, T, Span>> for Diagnostic {
+    fn from(err: Rich<'_, T, Span>) -> Self {
+        let span = *err.span();
+
+        let mut diagnostic = match err.reason() {
+            RichReason::Custom(message) => Diagnostic::error(message).with_label(span, ""),
+            _ => {
+                let found = err.found().map_or(String::from("end of input"), describe);
+
+                let mut expected = err
+                    .expected()
+                    .map(|pattern| match pattern {
+                        RichPattern::Token(token) => describe(&**token),
+                        RichPattern::Label(label) => label.to_string(),
+                        RichPattern::EndOfInput => String::from("end of input"),
+                    })
+                    .collect::<Vec<_>>();
+                expected.sort();
+                expected.dedup();
+
+                let label = match expected.as_slice() {
+                    [] => String::from("unexpected here"),
+                    expected => format!("expected {}", one_of(expected)),
+                };
+
+                Diagnostic::error(format!("unexpected {found}")).with_label(span, label)
+            }
+        };
+
+        // Only the innermost context, the rest are usually too broad to help
+        if let Some((label, context_span)) = err.contexts().next() {
+            diagnostic = diagnostic
+                .with_secondary_label(*context_span, format!("while parsing this {label}"));
+        }
+
+        diagnostic
+    }
+}
+
+impl From<&ExprError> for Diagnostic {
+    fn from(err: &ExprError) -> Self {
+        let mut diagnostic = Diagnostic::error(err.to_string());
+
+        let label = match err {
+            ExprError::TypeError { expected, .. } => format!("expected {expected}"),
+            ExprError::UnboundVariable { .. } => String::from("not found in this scope"),
+            ExprError::NotAFunction { found, .. } => format!("this has type {found}"),
+            ExprError::MissingField { field, .. } => format!("no field `{field}`"),
+            ExprError::UnknownConstructor { .. } => String::from("not found in this scope"),
+            ExprError::NoMatch { .. } => String::from("none of the arms match this value"),
+            ExprError::ArgumentMismatch { .. } => String::from("parameter declared here"),
+            ExprError::NotShowable { found, .. } => format!("this has type {found}"),
+            ExprError::CyclicDefinition { .. } => {
+                String::from("used here while its value is being computed")
+            }
+            ExprError::ArityOrOperandMismatch { .. }
+            | ExprError::DivisionByZero { .. }
+            | ExprError::RecursionLimit { .. }
+            | ExprError::Todo { .. } => String::new(),
+        };
+
+        if let Some(span) = err.span() {
+            diagnostic = diagnostic.with_label(span, label);
+        }
+
+        if let ExprError::MissingField { available, .. } = err {
+            diagnostic = diagnostic.with_note(match available.as_slice() {
+                [] => String::from("the record has no fields"),
+                available => format!("available fields are {}", available.join(", ")),
+            });
+        }
+
+        diagnostic
+    }
+}
+
+impl From<&TypeError> for Diagnostic {
+    fn from(err: &TypeError) -> Self {
+        let label = match err {
+            TypeError::Mismatch {
+                expected, found, ..
+            } => format!("expected `{expected}`, found `{found}`"),
+            TypeError::UnboundVariable { .. }
+            | TypeError::UnknownConstructor { .. }
+            | TypeError::UnknownType { .. } => String::from("not found in this scope"),
+            TypeError::NotAFunction { .. } => String::from("not a function"),
+            TypeError::NotARecord { .. } => String::from("not a record"),
+            TypeError::MissingField { field, .. } => format!("no field `{field}`"),
+            TypeError::ExistingField { .. } => String::from("already in the record"),
+            TypeError::TypeArity { expected, .. } => format!("expected {expected} arguments"),
+            TypeError::UnboundTypeVariable { .. } => String::from("not a parameter of the type"),
+            TypeError::UnknownClass { .. } => String::from("not a class"),
+            TypeError::NoInstance { class, .. } => format!("`{class}` is needed here"),
+            TypeError::BuiltinType { .. } => String::from("already declared by the prelude"),
+            TypeError::FieldName { .. } => String::from("not applied to a field name"),
+            TypeError::InfiniteType { .. } | TypeError::ConstructorPayload { .. } => String::new(),
+        };
+
+        let diagnostic = Diagnostic::error(err.to_string()).with_label(err.span(), label);
+
+        match err {
+            TypeError::MissingField { available, .. } => {
+                diagnostic.with_note(match available.as_slice() {
+                    [] => String::from("the record has no fields"),
+                    available => format!("available fields are {}", available.join(", ")),
+                })
+            }
+            TypeError::ExistingField { field, .. } => diagnostic.with_note(format!(
+                "to replace the field, update the record with `{{ record | {field}: ... }}`"
+            )),
+            TypeError::UnknownClass { .. } => {
+                diagnostic.with_note("the classes are `Add`, `Num`, `Eq`, `Ord` and `Show`")
+            }
+            TypeError::Mismatch {
+                expected, found, ..
+            } if matches!(
+                (expected.as_str(), found.as_str()),
+                ("Int", "Rational") | ("Rational", "Int")
+            ) =>
+            {
+                diagnostic.with_note(
+                    "numbers don't change type on their own; `rational x` is `x` as a `Rational`",
+                )
+            }
+            TypeError::NoInstance {
+                class,
+                ty,
+                variable: true,
+                ..
+            } => diagnostic.with_note(format!("add `{class} {ty} =>` to the annotation")),
+            TypeError::NoInstance { class, .. } => diagnostic.with_note(format!(
+                "the instances of `{class}` are {}",
+                class.members()
+            )),
+            _ => diagnostic,
+        }
+    }
+}
+
+impl From<&PatternWarning> for Diagnostic {
+    fn from(warning: &PatternWarning) -> Self {
+        let diagnostic = Diagnostic::warning(warning.to_string());
+
+        match warning {
+            PatternWarning::NonExhaustive { missing, span } => {
+                let patterns = missing
+                    .iter()
+                    .map(|pattern| format!("`{pattern}`"))
+                    .collect::<Vec<_>>();
+                let label = match patterns.as_slice() {
+                    [pattern] => format!("pattern {pattern} not covered"),
+                    [first, second, third, rest @ ..] if !rest.is_empty() => format!(
+                        "patterns {first}, {second}, {third} and {} more not covered",
+                        rest.len()
+                    ),
+                    [init @ .., last] => {
+                        format!("patterns {} and {last} not covered", init.join(", "))
+                    }
+                    [] => String::new(),
+                };
+                diagnostic
+                    .with_label(*span, label)
+                    .with_note("add an arm for each missing pattern, or a `_` arm for the rest")
+            }
+            PatternWarning::Unreachable { span } => {
+                diagnostic.with_label(*span, "the arms above already match these values")
+            }
+        }
+    }
+}
+
+/// How [`render`] should print a [`Diagnostic`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    Plain,
+    /// Plain, plus ANSI escape codes for the colors of a terminal.
+    Ansi,
+}
+
+impl Style {
+    fn paint(self, code: &str, text: impl Display) -> String {
+        match self {
+            Style::Plain => text.to_string(),
+            Style::Ansi => format!("\x1b[{code}m{text}\x1b[0m"),
+        }
+    }
+}
+
+const BOLD: &str = "1";
+const RED: &str = "1;31";
+const YELLOW: &str = "1;33";
+const BLUE: &str = "1;34";
+
+/// Renders a diagnostic like:
+///
+/// ```text
+/// error: unbound variable `foo`
+///  --> main.len:2:5
+///   |
+/// 2 | a = foo + 1
+///   |     ^^^ not found in this scope
+/// ```
+pub fn render(diagnostic: &Diagnostic, sources: &Sources, style: Style) -> String {
+    let mut out = String::new();
+    let color = match diagnostic.severity {
+        Severity::Error => RED,
+        Severity::Warning => YELLOW,
+    };
+
+    let _ = writeln!(
+        out,
+        "{}{}",
+        style.paint(color, diagnostic.severity),
+        style.paint(BOLD, format!(": {}", diagnostic.message))
+    );
+
+    let mut labels = diagnostic.labels.iter().collect::<Vec<_>>();
+    labels.sort_by_key(|label| (!label.primary, label.span.start));
+
+    let gutter = labels
+        .iter()
+        .filter_map(|label| {
+            let file = sources.file(&label.span)?;
+            Some(file.line_col(label.span.start).0 + 1)
+        })
+        .max()
+        .map_or(0, |line| line.to_string().len());
+    let pad = " ".repeat(gutter);
+
+    // The labels of every file, in the order in which the files first appear
+    let mut files: Vec<(&SourceFile, Vec<&Label>)> = Vec::new();
+    for label in labels {
+        match sources.file(&label.span) {
+            Some(file) => match files.iter_mut().find(|(f, _)| std::ptr::eq(*f, file)) {
+                Some((_, file_labels)) => file_labels.push(label),
+                None => files.push((file, vec![label])),
+            },
+            None => {
+                let _ = writeln!(
+                    out,
+                    "{pad} {} at {}: {}",
+                    style.paint(BLUE, "="),
+                    label.span,
+                    label.message
+                );
+            }
+        }
+    }
+
+    for (file, file_labels) in files {
+        let (line, column) = file.line_col(file_labels[0].span.start);
+        let _ = writeln!(
+            out,
+            "{pad}{} {}:{}:{}",
+            style.paint(BLUE, "-->"),
+            file.name(),
+            line + 1,
+            column + 1
+        );
+        let _ = writeln!(out, "{pad} {}", style.paint(BLUE, "|"));
+
+        let mut lines = file_labels
+            .iter()
+            .map(|label| file.line_col(label.span.start).0)
+            .collect::<Vec<_>>();
+        lines.sort();
+        lines.dedup();
+
+        for line in lines {
+            let text = file.line(line).replace('\t', " ");
+            let _ = writeln!(
+                out,
+                "{} {} {text}",
+                style.paint(BLUE, format!("{:>gutter$}", line + 1)),
+                style.paint(BLUE, "|")
+            );
+
+            let mut line_labels = file_labels
+                .iter()
+                .filter(|label| file.line_col(label.span.start).0 == line)
+                .collect::<Vec<_>>();
+            line_labels.sort_by_key(|label| label.span.start);
+
+            for label in line_labels {
+                let (_, start) = file.line_col(label.span.start);
+                let (end_line, end) = file.line_col(label.span.end);
+                // Spans that continue on the next lines are underlined until
+                // the end of their first line
+                let end = if end_line == line {
+                    end
+                } else {
+                    text.chars().count()
+                };
+                let width = end.saturating_sub(start).max(1);
+
+                let (marker, color) = if label.primary {
+                    ("^", color)
+                } else {
+                    ("-", BLUE)
+                };
+                let underline = style.paint(color, marker.repeat(width));
+                let message = if label.primary {
+                    style.paint(color, &label.message)
+                } else {
+                    style.paint(BLUE, &label.message)
+                };
+
+                let _ = writeln!(
+                    out,
+                    "{pad} {} {}{underline} {message}",
+                    style.paint(BLUE, "|"),
+                    " ".repeat(start)
+                );
+            }
+        }
+    }
+
+    for note in &diagnostic.notes {
+        let _ = writeln!(out, "{pad} {} note: {note}", style.paint(BLUE, "="));
+    }
+
+    // Labels without a message leave a trailing space behind
+    out.lines()
+        .map(str::trim_end)
+        .fold(String::new(), |acc, line| acc + line + "\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::complete::parse;
+    use crate::eval::{eval_program, Scope};
+    use crate::infer::{check_program, Env};
+    use insta::assert_snapshot;
+    use rstest::rstest;
+
+    fn diagnose(input: &str) -> Vec<Diagnostic> {
+        let mut sources = Sources::new();
+        let file = sources.add("main.len", input);
+
+        match parse(&sources, file) {
+            Ok(program) => match check_program(&program, &Env::std()) {
+                Ok(checked) => {
+                    let mut diagnostics = checked
+                        .warnings
+                        .iter()
+                        .map(Diagnostic::from)
+                        .collect::<Vec<_>>();
+                    if let Err(err) = eval_program(program, Scope::std()) {
+                        diagnostics.push(Diagnostic::from(&err));
+                    }
+                    diagnostics
+                }
+                Err(err) => vec![Diagnostic::from(&err)],
+            },
+            Err(diagnostics) => diagnostics,
+        }
+    }
+
+    fn render_all(input: &str, style: Style) -> String {
+        let mut sources = Sources::new();
+        sources.add("main.len", input);
+
+        diagnose(input)
+            .iter()
+            .map(|d| render(d, &sources, style))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[rstest]
+    fn test_render(
+        #[values(
+            ("unterminated_string", "a = \"foo"),
+            ("unclosed_group", "a = (1 + 2\nb = a"),
+            ("expression_not_last", "1\n2"),
+            ("unbound", "a = 1\nb = a + c"),
+            ("not_a_function", "f = 1\nf 2"),
+            ("missing_field", "r = { a: 1, b: 2 }\nget r \"c\""),
+            ("duplicate_field", "r = { a: 1, b: 2, a: 3 }"),
+            ("cyclic_definition", "a = b + 1\nb = a"),
+            ("no_match", "type Option a = Some a | None\nmatch Some 1 {\n  None => 0,\n}"),
+            ("duplicate_binding", "a = 1\nb = let c = 1; c = 2 in c\na = 2"),
+            ("type_mismatch", "f = n => n + 1\nf \"a\""),
+            ("type_missing_field", "r = { a: 1, b: 2 }\nr.c"),
+            ("existing_field", "r = { a: 1 }\n{ a: 2 | r }"),
+            ("non_exhaustive", "type Color = Red | Green | Blue\nmatch Green {\n  Green => 0,\n}"),
+            ("unreachable_arm", "match 1 {\n  _ => 0,\n  1 => 1,\n}"),
+            ("no_instance", "f = x y => x == y\nf inc inc"),
+            ("annotation_without_class", "add : a -> a -> a = x y => x + y"),
+            ("malformed_number", "mask = 0x1F\nmode = 0o7S5"),
+            ("rational_mismatch", "half = x => x * 0.5\ninc (half 3)"),
+            ("mixed_numbers", "count = 3\ncount * 0.5"),
+            ("division_by_zero", "x = 1\ny = x - 1\nx / y"),
+            ("unknown_escape", "path = \"C:\\dir\""),
+            ("interpolated_function", "greet = name => \"hello ${name}\"\ngreet (x => x)"),
+            ("unclosed_interpolation", "x = \"a ${b + 1\""),
+            ("block_string_escape", "script = \"\"\"\n  echo ${name}\n  cd \\q\n  \"\"\""),
+            ("unterminated_block_comment", "x = 1 #[ note #[ nested ]#\ny = 2"),
+            ("unexpected_characters", "x = 1 ` 2\ny = [3]"),
+        )]
+        input: (&str, &str),
+    ) {
+        assert_snapshot!(input.0, render_all(input.1, Style::Plain));
+    }
+
+    #[test]
+    fn test_render_ansi() {
+        assert_snapshot!(render_all("(x => y) 1", Style::Ansi));
+    }
+}
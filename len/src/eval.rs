@@ -1,12 +1,18 @@
-use chumsky::primitive::todo;
 use thiserror::Error;
-use tracing::{debug, error, info, warn};
 
 use crate::ast::{self};
-use crate::Int;
+use crate::class::{Class, Head, Instance, LIST};
+use crate::lexer::Span;
+use crate::number::format_rational;
+use crate::ty::Primitive;
+use crate::{Int, Rational};
+use num::Zero;
+use std::cell::{Cell, RefCell};
+use std::cmp::Ordering;
+use std::collections::HashMap;
 use std::fmt;
-use std::ops::{Deref, DerefMut};
-use std::{collections::HashMap, error::Error, fmt::Display, rc::Rc};
+use std::ops::Deref;
+use std::rc::{Rc, Weak};
 
 #[derive(Debug, Clone)]
 pub struct Object {
@@ -42,28 +48,245 @@ impl Object {
         }
     }
 
+    fn new_rational(rational: Rational) -> Self {
+        Object {
+            ptr: From::from(RawObject::Rational(Box::new(rational))),
+        }
+    }
+
+    fn new_bool(b: bool) -> Self {
+        Object {
+            ptr: From::from(RawObject::Bool(b)),
+        }
+    }
+
     fn new_string(input: String) -> Self {
         Object {
             ptr: From::from(RawObject::String(input)),
         }
     }
+
+    fn new_variant(ty: String, tag: String, payload: Option<Object>) -> Self {
+        Object {
+            ptr: From::from(RawObject::Variant { ty, tag, payload }),
+        }
+    }
+
+    fn new_tuple(elements: Vec<Object>) -> Self {
+        Object {
+            ptr: From::from(RawObject::Tuple(elements)),
+        }
+    }
+
+    fn new_product(fields: HashMap<String, Object>) -> Self {
+        Object {
+            ptr: From::from(RawObject::Product(fields)),
+        }
+    }
 }
 
 #[derive(Debug)]
 pub enum RawObject {
     Int(Int),
+    /// Boxed to keep every other value small.
+    Rational(Box<Rational>),
+    Bool(bool),
     String(String),
     Function(Function),
     Product(HashMap<String, Object>),
+    Tuple(Vec<Object>),
+    /// A value built by a constructor of the sum type `ty`.
+    Variant {
+        ty: String,
+        tag: String,
+        payload: Option<Object>,
+    },
+}
+
+impl fmt::Display for Object {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.ptr.fmt(f)
+    }
+}
+
+impl fmt::Display for RawObject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RawObject::Int(i) => write!(f, "{i}"),
+            RawObject::Rational(r) => write!(f, "{}", format_rational(r)),
+            RawObject::Bool(b) => write!(f, "{b}"),
+            RawObject::String(s) => write!(f, "{s:?}"),
+            RawObject::Function(_) => write!(f, "<function>"),
+            RawObject::Product(fields) if fields.is_empty() => write!(f, "{{}}"),
+            RawObject::Product(fields) => {
+                let mut fields = fields.iter().collect::<Vec<_>>();
+                fields.sort_by_key(|(k, _)| *k);
+
+                write!(f, "{{")?;
+                for (i, (k, v)) in fields.into_iter().enumerate() {
+                    let sep = if i == 0 { " " } else { ", " };
+                    write!(f, "{sep}{k}: {v}")?;
+                }
+                write!(f, " }}")
+            }
+            RawObject::Tuple(elements) => {
+                write!(f, "(")?;
+                for (i, element) in elements.iter().enumerate() {
+                    let sep = if i == 0 { "" } else { ", " };
+                    write!(f, "{sep}{element}")?;
+                }
+                write!(f, ")")
+            }
+            RawObject::Variant { tag, payload, .. } => match payload {
+                None => write!(f, "{tag}"),
+                Some(payload) => match &**payload {
+                    RawObject::Function(_)
+                    | RawObject::Variant {
+                        payload: Some(_), ..
+                    } => write!(f, "{tag} ({payload})"),
+                    _ => write!(f, "{tag} {payload}"),
+                },
+            },
+        }
+    }
 }
 
 impl RawObject {
+    pub fn type_name(&self) -> &str {
+        match self {
+            RawObject::Int(_) => "Int",
+            RawObject::Rational(_) => "Rational",
+            RawObject::Bool(_) => "Bool",
+            RawObject::String(_) => "String",
+            RawObject::Function(_) => "Function",
+            RawObject::Product(_) => "Record",
+            RawObject::Tuple(_) => "Tuple",
+            RawObject::Variant { ty, .. } => ty,
+        }
+    }
+
+    /// The outermost constructor of the type of the value, which picks the
+    /// instance of a class like the type itself does in the type checker.
+    pub fn head(&self) -> Head<'_> {
+        match self {
+            RawObject::Int(_) => Head::Primitive(Primitive::Int),
+            RawObject::Rational(_) => Head::Primitive(Primitive::Rational),
+            RawObject::Bool(_) => Head::Primitive(Primitive::Bool),
+            RawObject::String(_) => Head::Primitive(Primitive::String),
+            RawObject::Function(_) => Head::Function,
+            RawObject::Product(_) => Head::Record,
+            RawObject::Tuple(_) => Head::Tuple,
+            RawObject::Variant { ty, .. } => Head::Sum(ty),
+        }
+    }
+
+    /// Structural equality, `None` for values that can't be compared, such
+    /// as functions or values of different types.
+    fn equals(&self, other: &RawObject) -> Option<bool> {
+        match (self, other) {
+            (RawObject::Int(a), RawObject::Int(b)) => Some(a == b),
+            (RawObject::Int(_) | RawObject::Rational(_), _) => {
+                let (a, b) = promote(self, other)?;
+                Some(a == b)
+            }
+            (RawObject::Bool(a), RawObject::Bool(b)) => Some(a == b),
+            (RawObject::String(a), RawObject::String(b)) => Some(a == b),
+            (RawObject::Product(a), RawObject::Product(b)) => {
+                if a.len() != b.len() {
+                    return Some(false);
+                }
+                let mut equal = true;
+                for (k, v) in a {
+                    match b.get(k) {
+                        Some(w) => equal &= v.equals(w)?,
+                        None => return Some(false),
+                    }
+                }
+                Some(equal)
+            }
+            (RawObject::Tuple(a), RawObject::Tuple(b)) if a.len() == b.len() => {
+                let mut equal = true;
+                for (a, b) in a.iter().zip(b) {
+                    equal &= a.equals(b)?;
+                }
+                Some(equal)
+            }
+            (
+                RawObject::Variant {
+                    ty: a_ty,
+                    tag: a_tag,
+                    payload: a,
+                },
+                RawObject::Variant {
+                    ty: b_ty,
+                    tag: b_tag,
+                    payload: b,
+                },
+            ) if a_ty == b_ty => match (a, b) {
+                _ if a_tag != b_tag => Some(false),
+                (Some(a), Some(b)) => a.equals(b),
+                _ => Some(true),
+            },
+            _ => None,
+        }
+    }
+
+    /// The fields of a record.
+    fn fields(&self) -> ExprResult<&HashMap<String, Object>> {
+        match self {
+            RawObject::Product(fields) => Ok(fields),
+            other => Err(ExprError::TypeError {
+                expected: String::from("Record"),
+                found: other.type_name().to_string(),
+                span: None,
+            }),
+        }
+    }
+
+    fn field(&self, name: &str) -> ExprResult<Object> {
+        let fields = self.fields()?;
+        fields
+            .get(name)
+            .cloned()
+            .ok_or_else(|| missing_field(name, fields))
+    }
+
+    /// Whether the value has a function in it, which may hold on to the
+    /// scope that it was made in.
+    fn has_function(&self) -> bool {
+        // Lists nest as deep as they are long, so this doesn't recurse
+        let mut pending = vec![self];
+        while let Some(value) = pending.pop() {
+            match value {
+                RawObject::Function(_) => return true,
+                RawObject::Product(fields) => pending.extend(fields.values().map(|v| &**v)),
+                RawObject::Tuple(elements) => pending.extend(elements.iter().map(|e| &**e)),
+                RawObject::Variant {
+                    payload: Some(payload),
+                    ..
+                } => pending.push(payload),
+                _ => {}
+            }
+        }
+        false
+    }
+
+    /// The value of a number as a rational.
+    fn rational(&self) -> Option<Rational> {
+        match self {
+            RawObject::Int(i) => Some(Rational::from_integer(i.clone())),
+            RawObject::Rational(r) => Some(*r.clone()),
+            _ => None,
+        }
+    }
+
     fn assume_int(&self) -> ExprResult<&Int> {
         match self {
             RawObject::Int(i) => Ok(i),
             other => Err(ExprError::TypeError {
                 expected: String::from("Int"),
-                found: format!("{:?}", other),
+                found: other.type_name().to_string(),
+                span: None,
             }),
         }
     }
@@ -79,20 +302,286 @@ impl fmt::Debug for Function {
     }
 }
 
+fn missing_field(name: &str, fields: &HashMap<String, Object>) -> ExprError {
+    let mut available = fields.keys().cloned().collect::<Vec<_>>();
+    available.sort();
+    ExprError::MissingField {
+        field: name.to_string(),
+        available,
+        span: None,
+    }
+}
+
+/// Errors raised while evaluating.
+///
+/// Built-in functions don't know where they were called from, so they leave
+/// `span` empty and [`eval_expr`] fills it with the span of the call.
 #[derive(Debug, Clone, Error)]
 pub enum ExprError {
-    #[error("Type error")]
-    TypeError { expected: String, found: String },
-    #[error("Todo")]
-    Todo,
+    #[error("expected a value of type {expected}, found {found}")]
+    TypeError {
+        expected: String,
+        found: String,
+        span: Option<Span>,
+    },
+    #[error("unbound variable `{name}`")]
+    UnboundVariable { name: String, span: Option<Span> },
+    #[error("a value of type {found} is not a function")]
+    NotAFunction { found: String, span: Option<Span> },
+    #[error("record has no field `{field}`")]
+    MissingField {
+        field: String,
+        available: Vec<String>,
+        span: Option<Span>,
+    },
+    #[error("`{operator}` can't be applied to {}", operands.join(" and "))]
+    ArityOrOperandMismatch {
+        operator: String,
+        operands: Vec<String>,
+        span: Option<Span>,
+    },
+    #[error("`{name}` depends on its own value")]
+    CyclicDefinition { name: String, span: Option<Span> },
+    #[error("unknown constructor `{name}`")]
+    UnknownConstructor { name: String, span: Option<Span> },
+    #[error("no pattern matches the value `{value}`")]
+    NoMatch { value: String, span: Option<Span> },
+    #[error("the argument `{value}` doesn't match the parameter of the function")]
+    ArgumentMismatch { value: String, span: Option<Span> },
+    #[error("a value of type {found} can't be written into a string")]
+    NotShowable { found: String, span: Option<Span> },
+    #[error("division by zero")]
+    DivisionByZero { span: Option<Span> },
+    #[error("function calls are nested more than {limit} deep")]
+    RecursionLimit { limit: usize, span: Option<Span> },
+    #[error("evaluation of this expression is not implemented yet")]
+    Todo { span: Option<Span> },
 }
 
-type ExprResult<T> = Result<T, ExprError>;
+impl ExprError {
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            ExprError::TypeError { span, .. }
+            | ExprError::UnboundVariable { span, .. }
+            | ExprError::NotAFunction { span, .. }
+            | ExprError::MissingField { span, .. }
+            | ExprError::ArityOrOperandMismatch { span, .. }
+            | ExprError::CyclicDefinition { span, .. }
+            | ExprError::UnknownConstructor { span, .. }
+            | ExprError::NoMatch { span, .. }
+            | ExprError::ArgumentMismatch { span, .. }
+            | ExprError::NotShowable { span, .. }
+            | ExprError::DivisionByZero { span }
+            | ExprError::RecursionLimit { span, .. }
+            | ExprError::Todo { span } => *span,
+        }
+    }
 
-#[derive(Debug, Clone)]
+    /// Sets the span of the error, unless it already points somewhere more
+    /// precise.
+    fn or_span(mut self, new: Span) -> Self {
+        match &mut self {
+            ExprError::TypeError { span, .. }
+            | ExprError::UnboundVariable { span, .. }
+            | ExprError::NotAFunction { span, .. }
+            | ExprError::MissingField { span, .. }
+            | ExprError::ArityOrOperandMismatch { span, .. }
+            | ExprError::CyclicDefinition { span, .. }
+            | ExprError::UnknownConstructor { span, .. }
+            | ExprError::NoMatch { span, .. }
+            | ExprError::ArgumentMismatch { span, .. }
+            | ExprError::NotShowable { span, .. }
+            | ExprError::DivisionByZero { span }
+            | ExprError::RecursionLimit { span, .. }
+            | ExprError::Todo { span } => {
+                span.get_or_insert(new);
+            }
+        }
+        self
+    }
+}
+
+pub type ExprResult<T> = Result<T, ExprError>;
+
+/// Curried function of two arguments.
+fn binary<F>(func: F) -> Object
+where
+    F: Fn(&Object, &Object) -> ExprResult<Object> + Clone + 'static,
+{
+    Object::new_function(move |left| {
+        let func = func.clone();
+        Ok(Object::new_function(move |right| func(&left, &right)))
+    })
+}
+
+fn operand_mismatch(operator: &str, left: &RawObject, right: &RawObject) -> ExprError {
+    ExprError::ArityOrOperandMismatch {
+        operator: operator.to_string(),
+        operands: vec![left.type_name().to_string(), right.type_name().to_string()],
+        span: None,
+    }
+}
+
+/// Both operands as rationals, if they are numbers.
+fn promote(left: &RawObject, right: &RawObject) -> Option<(Rational, Rational)> {
+    Some((left.rational()?, right.rational()?))
+}
+
+/// Applies `int` to two integers, and `rational` to any other two numbers.
+fn arithmetic(
+    left: &RawObject,
+    right: &RawObject,
+    int: fn(&Int, &Int) -> Int,
+    rational: fn(&Rational, &Rational) -> Rational,
+) -> Option<Object> {
+    match (left, right) {
+        (RawObject::Int(a), RawObject::Int(b)) => Some(Object::new_int(int(a, b))),
+        _ => {
+            let (a, b) = promote(left, right)?;
+            Some(Object::new_rational(rational(&a, &b)))
+        }
+    }
+}
+
+/// Arithmetic operator, with the instance of [`Class::Num`] of its operands.
+fn number_operator(
+    name: &'static str,
+    int: fn(&Int, &Int) -> Int,
+    rational: fn(&Rational, &Rational) -> Rational,
+) -> Object {
+    binary(move |left, right| {
+        let result = match Class::Num.instance(left.head()) {
+            Some(Instance::Arithmetic) => arithmetic(left, right, int, rational),
+            _ => None,
+        };
+        result.ok_or_else(|| operand_mismatch(name, left, right))
+    })
+}
+
+/// `/`, which divides any two numbers into a rational.
+fn divide_operator() -> Object {
+    binary(|left, right| {
+        let (a, b) = promote(left, right).ok_or_else(|| operand_mismatch("/", left, right))?;
+        if b.is_zero() {
+            return Err(ExprError::DivisionByZero { span: None });
+        }
+        Ok(Object::new_rational(a / b))
+    })
+}
+
+/// `+`, with the instance of [`Class::Add`] of its operands.
+fn add_operator() -> Object {
+    binary(|left, right| {
+        let sum = match (Class::Add.instance(left.head()), &**left, &**right) {
+            (Some(Instance::Arithmetic), _, _) => {
+                arithmetic(left, right, |a, b| a + b, |a, b| a + b)
+            }
+            (Some(Instance::StringConcatenation), RawObject::String(a), RawObject::String(b)) => {
+                Some(Object::new_string(format!("{a}{b}")))
+            }
+            (Some(Instance::ListConcatenation), _, _) => concatenate(left, right),
+            _ => None,
+        };
+        sum.ok_or_else(|| operand_mismatch("+", left, right))
+    })
+}
+
+/// Appends the elements of the list `right` to the list `left`, `None` if
+/// they aren't both lists.
+fn concatenate(left: &Object, right: &Object) -> Option<Object> {
+    if right.head() != Head::Sum(LIST) {
+        return None;
+    }
+
+    let mut elements = Vec::new();
+    let mut list = left;
+    loop {
+        match &**list {
+            RawObject::Variant {
+                ty, payload: None, ..
+            } if ty == LIST => break,
+            RawObject::Variant {
+                ty,
+                payload: Some(payload),
+                ..
+            } if ty == LIST => match &**payload {
+                RawObject::Tuple(cons) if cons.len() == 2 => {
+                    elements.push(cons[0].clone());
+                    list = &cons[1];
+                }
+                _ => return None,
+            },
+            _ => return None,
+        }
+    }
+
+    Some(
+        elements
+            .into_iter()
+            .rev()
+            .fold(right.clone(), |tail, head| {
+                let cons = Object::new_tuple(vec![head, tail]);
+                Object::new_variant(LIST.to_string(), String::from("Cons"), Some(cons))
+            }),
+    )
+}
+
+/// Equality operator, `==` or its negation `!=`, with the instance of
+/// [`Class::Eq`] of its operands.
+fn equality_operator(name: &'static str, negate: bool) -> Object {
+    binary(move |left, right| {
+        let equal = match Class::Eq.instance(left.head()) {
+            Some(Instance::StructuralEquality) => left.equals(right),
+            _ => None,
+        };
+        match equal {
+            Some(equal) => Ok(Object::new_bool(equal != negate)),
+            None => Err(operand_mismatch(name, left, right)),
+        }
+    })
+}
+
+/// Ordering operator, with the instance of [`Class::Ord`] of its operands.
+fn comparison_operator(name: &'static str, op: fn(Ordering) -> bool) -> Object {
+    binary(move |left, right| {
+        let ordering = match (Class::Ord.instance(left.head()), &**left, &**right) {
+            (Some(Instance::Comparison), RawObject::Int(a), RawObject::Int(b)) => Some(a.cmp(b)),
+            (Some(Instance::Comparison), RawObject::String(a), RawObject::String(b)) => {
+                Some(a.cmp(b))
+            }
+            (Some(Instance::Comparison), _, _) => promote(left, right).map(|(a, b)| a.cmp(&b)),
+            _ => None,
+        };
+        match ordering {
+            Some(ordering) => Ok(Object::new_bool(op(ordering))),
+            None => Err(operand_mismatch(name, left, right)),
+        }
+    })
+}
+
+#[derive(Debug, Clone, Default)]
 pub struct RawScope {
     pub parent: Option<Scope>,
     pub bindings: HashMap<String, Object>,
+    /// Bindings of a recursive group, see [`Scope::recursive`].
+    thunks: RefCell<HashMap<String, Thunk>>,
+}
+
+/// A binding of a recursive group, evaluated the first time it is looked up.
+#[derive(Debug, Clone)]
+enum Thunk {
+    Pending(ast::Expr),
+    /// Finding a binding in this state again means that it depends on itself.
+    Evaluating,
+    Done(Object),
+    /// A value with functions in it, which can hold on to the scope of the
+    /// group. The scope only keeps it while something else uses it, and
+    /// evaluates `expr` again otherwise, so that the two don't keep each
+    /// other alive.
+    Shared {
+        expr: ast::Expr,
+        value: Weak<RawObject>,
+    },
 }
 
 #[derive(Debug, Clone)]
@@ -102,47 +591,39 @@ impl RawScope {
     fn std() -> Self {
         let mut bindings = HashMap::new();
 
+        bindings.insert(String::from("+"), add_operator());
         bindings.insert(
-            String::from("+"),
-            Object::new_function(|x| {
-                let x = x.clone();
-                Ok(Object::new_function(move |y| match (&*x, &*y) {
-                    (RawObject::Int(a), RawObject::Int(b)) => Ok(Object::new_int(a + b)),
-                    _ => todo!(),
-                }))
-            }),
+            String::from("-"),
+            number_operator("-", |a, b| a - b, |a, b| a - b),
         );
-
         bindings.insert(
-            String::from("-"),
-            Object::new_function(|left| {
-                let left = left.clone();
-                Ok(Object::new_function(move |right| match (&*left, &*right) {
-                    (RawObject::Int(a), RawObject::Int(b)) => Ok(Object::new_int(a - b)),
-                    _ => todo!(),
-                }))
-            }),
+            String::from("*"),
+            number_operator("*", |a, b| a * b, |a, b| a * b),
         );
 
+        bindings.insert(String::from("/"), divide_operator());
+
+        bindings.insert(String::from("=="), equality_operator("==", false));
+        bindings.insert(String::from("!="), equality_operator("!=", true));
+        bindings.insert(String::from("<"), comparison_operator("<", Ordering::is_lt));
         bindings.insert(
-            String::from("*"),
-            Object::new_function(|left| {
-                let left = left.clone();
-                Ok(Object::new_function(move |right| match (&*left, &*right) {
-                    (RawObject::Int(a), RawObject::Int(b)) => Ok(Object::new_int(a * b)),
-                    _ => todo!(),
-                }))
-            }),
+            String::from("<="),
+            comparison_operator("<=", Ordering::is_le),
+        );
+        bindings.insert(String::from(">"), comparison_operator(">", Ordering::is_gt));
+        bindings.insert(
+            String::from(">="),
+            comparison_operator(">=", Ordering::is_ge),
         );
 
         bindings.insert(
             String::from("$"),
-            Object::new_function(|left| {
-                let left = left.clone();
-                Ok(Object::new_function(move |right| match &*left {
-                    RawObject::Function(f) => (f.value)(right),
-                    _ => todo!(),
-                }))
+            binary(|left, right| match &**left {
+                RawObject::Function(f) => (f.value)(right.clone()),
+                other => Err(ExprError::NotAFunction {
+                    found: other.type_name().to_string(),
+                    span: None,
+                }),
             }),
         );
 
@@ -151,45 +632,173 @@ impl RawScope {
             Object::new_function(|x| x.assume_int().map(|i| i + 1).map(Object::new_int)),
         );
 
+        bindings.insert(
+            String::from("rational"),
+            Object::new_function(|x| {
+                x.rational()
+                    .map(Object::new_rational)
+                    .ok_or_else(|| ExprError::TypeError {
+                        expected: String::from("Int or Rational"),
+                        found: x.type_name().to_string(),
+                        span: None,
+                    })
+            }),
+        );
+
         bindings.insert(
             String::from("get"),
-            Object::new_function(|left| {
-                let left = left.clone();
-                Ok(Object::new_function(move |right| match (&*left, &*right) {
-                    (RawObject::Product(p), RawObject::String(s)) => {
-                        Ok(p.get(s).expect("Failed to get element").clone())
-                    }
-                    _ => todo!(),
-                }))
+            binary(|left, right| match &**right {
+                RawObject::String(s) => left.field(s),
+                other => Err(ExprError::TypeError {
+                    expected: String::from("String"),
+                    found: other.type_name().to_string(),
+                    span: None,
+                }),
             }),
         );
 
+        bindings.extend(constructors(&crate::prelude::types()));
+
         Self {
-            parent: None,
             bindings,
+            ..Default::default()
         }
     }
 }
 
+/// The constructors of the declared types. Those with a payload are
+/// functions that take it.
+fn constructors(types: &[ast::TypeDecl]) -> HashMap<String, Object> {
+    let mut bindings = HashMap::new();
+
+    for decl in types {
+        for variant in &decl.variants {
+            let ty = decl.name.name.clone();
+            let tag = variant.name.name.clone();
+            let constructor = match variant.payload {
+                None => Object::new_variant(ty, tag.clone(), None),
+                Some(_) => Object::new_function(move |payload| {
+                    Ok(Object::new_variant(ty.clone(), tag.clone(), Some(payload)))
+                }),
+            };
+            bindings.insert(variant.name.name.clone(), constructor);
+        }
+    }
+
+    bindings
+}
+
 impl Scope {
     pub fn std() -> Self {
         Scope(From::from(RawScope::std()))
     }
 
+    /// Looks a name up, evaluating it first if it belongs to a recursive
+    /// group and hasn't been needed yet.
     #[tracing::instrument(ret, level = "debug")]
-    pub fn symbol_lookup<S: AsRef<str> + fmt::Debug>(&self, symbol: S) -> Option<Object> {
-        match self.bindings.get(&symbol.as_ref().to_string()) {
-            Some(o) => Some(o.clone()),
-            None => match self.parent.clone() {
-                None => None,
-                Some(p) => p.symbol_lookup(symbol),
-            },
+    pub fn symbol_lookup<S: AsRef<str> + fmt::Debug>(
+        &self,
+        symbol: S,
+    ) -> ExprResult<Option<Object>> {
+        if let Some(o) = self.bindings.get(symbol.as_ref()) {
+            return Ok(Some(o.clone()));
+        }
+        if let Some(o) = self.force(symbol.as_ref())? {
+            return Ok(Some(o));
+        }
+        match self.parent.clone() {
+            None => Ok(None),
+            Some(p) => p.symbol_lookup(symbol),
         }
     }
 
     pub fn from_raw(raw: RawScope) -> Self {
         Self(From::from(raw))
     }
+
+    /// A child scope with the constructors of the declared types.
+    pub fn with_types(parent: Scope, types: &[ast::TypeDecl]) -> Self {
+        Scope::from_raw(RawScope {
+            parent: Some(parent),
+            bindings: constructors(types),
+            ..Default::default()
+        })
+    }
+
+    /// A child scope where every binding sees all the others, and itself.
+    ///
+    /// Right hand sides are evaluated when they are first looked up, so they
+    /// can appear in any order. Every binding is evaluated before returning,
+    /// to report its errors.
+    pub fn recursive(parent: Scope, bindings: Vec<ast::Binding>) -> ExprResult<Self> {
+        let names = bindings
+            .iter()
+            .map(|binding| binding.lhs.name.clone())
+            .collect::<Vec<_>>();
+
+        let thunks = bindings
+            .into_iter()
+            .map(|binding| (binding.lhs.name, Thunk::Pending(binding.rhs)))
+            .collect();
+
+        let scope = Scope::from_raw(RawScope {
+            parent: Some(parent),
+            thunks: RefCell::new(thunks),
+            ..Default::default()
+        });
+
+        for name in names {
+            scope.force(&name)?;
+        }
+
+        Ok(scope)
+    }
+
+    /// Evaluates a binding of the recursive group of this scope, if it has
+    /// one with that name.
+    fn force(&self, name: &str) -> ExprResult<Option<Object>> {
+        let mut thunks = self.thunks.borrow_mut();
+        let Some(thunk) = thunks.get_mut(name) else {
+            return Ok(None);
+        };
+        let expr = match std::mem::replace(thunk, Thunk::Evaluating) {
+            Thunk::Pending(expr) => expr,
+            Thunk::Done(value) => {
+                *thunk = Thunk::Done(value.clone());
+                return Ok(Some(value));
+            }
+            Thunk::Shared { expr, value } => match value.upgrade() {
+                Some(ptr) => {
+                    *thunk = Thunk::Shared { expr, value };
+                    return Ok(Some(Object { ptr }));
+                }
+                None => expr,
+            },
+            Thunk::Evaluating => {
+                return Err(ExprError::CyclicDefinition {
+                    name: name.to_string(),
+                    span: None,
+                })
+            }
+        };
+        // The right hand side may look up other bindings of the group
+        drop(thunks);
+
+        // The binding can be looked up again after an error, as the scopes of
+        // the REPL outlive the lines that fail
+        let result = eval_expr(expr.clone(), self.clone());
+        let thunk = match &result {
+            Ok(value) if value.has_function() => Thunk::Shared {
+                expr,
+                value: Rc::downgrade(&value.ptr),
+            },
+            Ok(value) => Thunk::Done(value.clone()),
+            Err(_) => Thunk::Pending(expr),
+        };
+        self.thunks.borrow_mut().insert(name.to_string(), thunk);
+
+        result.map(Some)
+    }
 }
 
 impl Deref for Scope {
@@ -200,79 +809,536 @@ impl Deref for Scope {
     }
 }
 
+fn eval_literal(literal: ast::Literal) -> Object {
+    match literal {
+        ast::Literal::Integer(x) => Object::new_int(x),
+        ast::Literal::Decimal(x) => Object::new_rational(*x),
+        ast::Literal::String(x) => Object::new_string(x),
+        ast::Literal::Boolean(b) => Object::new_bool(b),
+    }
+}
+
+/// Writes the values of the embedded expressions into the string, with the
+/// [`Class::Show`] instance of each. Strings go in without their quotes.
+fn eval_interpolation(parts: Vec<ast::StringPart>, scope: Scope) -> ExprResult<Object> {
+    let mut result = String::new();
+    for part in parts {
+        let expr = match part {
+            ast::StringPart::Text(text) => {
+                result.push_str(&text);
+                continue;
+            }
+            ast::StringPart::Expr(expr) => expr,
+        };
+        let span = expr.span;
+        let value = eval_expr(expr, scope.clone())?;
+        match (Class::Show.instance(value.head()), &*value) {
+            (Some(Instance::StructuralDisplay), RawObject::String(s)) => result.push_str(s),
+            (Some(Instance::StructuralDisplay), other) => result.push_str(&other.to_string()),
+            (_, other) => {
+                return Err(ExprError::NotShowable {
+                    found: other.type_name().to_string(),
+                    span: Some(span),
+                })
+            }
+        }
+    }
+    Ok(Object::new_string(result))
+}
+
 pub fn eval_expr(ast: ast::Expr, scope: Scope) -> ExprResult<Object> {
-    match ast {
-        ast::Expr::Literal(lit) => match lit {
-            ast::Literal::Integer(x) => Ok(Object::new_int(x)),
-            ast::Literal::String(x) => Ok(Object::new_string(x)),
-            _ => Err(ExprError::Todo),
+    let span = ast.span;
+    match ast.kind {
+        ast::ExprKind::Literal(lit) => Ok(eval_literal(lit)),
+        ast::ExprKind::Identifier(ident) => match scope
+            .symbol_lookup(&ident.name)
+            .map_err(|e| e.or_span(ident.span))?
+        {
+            Some(value) => Ok(value),
+            None => Err(ExprError::UnboundVariable {
+                name: ident.name,
+                span: Some(ident.span),
+            }),
         },
-        ast::Expr::Identifier(ident) => Ok(scope.symbol_lookup(ident.name).unwrap()),
-        ast::Expr::FunctionCall(call) => {
-            let function = eval_expr(*call.function, scope.clone()).unwrap();
-            let argument = eval_expr(*call.argument, scope.clone()).unwrap();
+        ast::ExprKind::FunctionCall(call) => {
+            let function_span = call.function.span;
+            let function = eval_expr(*call.function, scope.clone())?;
+            let argument = eval_expr(*call.argument, scope.clone())?;
             match *function {
-                RawObject::Function(ref f) => (f.value)(argument),
-                _ => Err(ExprError::TypeError {
-                    expected: todo!(),
-                    found: todo!(),
+                RawObject::Function(ref f) => (f.value)(argument).map_err(|e| e.or_span(span)),
+                ref other => Err(ExprError::NotAFunction {
+                    found: other.type_name().to_string(),
+                    span: Some(function_span),
                 }),
             }
         }
-        ast::Expr::Todo => todo!(),
-        ast::Expr::Product(_) => todo!(),
-        ast::Expr::Lambda(ast::Lambda { from, to }) => {
+        ast::ExprKind::Product(fields) => {
+            let fields = fields
+                .into_iter()
+                .map(|(name, value)| Ok((name.name, eval_expr(value, scope.clone())?)))
+                .collect::<ExprResult<HashMap<_, _>>>()?;
+            Ok(Object::new_product(fields))
+        }
+        ast::ExprKind::Tuple(elements) => {
+            let elements = elements
+                .into_iter()
+                .map(|element| eval_expr(element, scope.clone()))
+                .collect::<ExprResult<Vec<_>>>()?;
+            Ok(Object::new_tuple(elements))
+        }
+        ast::ExprKind::FieldAccess(ast::FieldAccess { record, field }) => {
+            let record_span = record.span;
+            let record = eval_expr(*record, scope)?;
+            record.field(&field.name).map_err(|e| match e {
+                ExprError::MissingField { .. } => e.or_span(field.span),
+                e => e.or_span(record_span),
+            })
+        }
+        ast::ExprKind::Ascription(ascription) => eval_expr(*ascription.expr, scope),
+        ast::ExprKind::Extension(ast::Extension { fields, record }) => {
+            eval_record_copy(*record, fields, false, scope)
+        }
+        ast::ExprKind::Update(ast::Update { record, fields }) => {
+            eval_record_copy(*record, fields, true, scope)
+        }
+        ast::ExprKind::If(ast::If {
+            condition,
+            then_branch,
+            else_branch,
+        }) => {
+            let condition_span = condition.span;
+            match *eval_expr(*condition, scope.clone())? {
+                RawObject::Bool(true) => eval_expr(*then_branch, scope),
+                RawObject::Bool(false) => eval_expr(*else_branch, scope),
+                ref other => Err(ExprError::TypeError {
+                    expected: String::from("Bool"),
+                    found: other.type_name().to_string(),
+                    span: Some(condition_span),
+                }),
+            }
+        }
+        ast::ExprKind::Let(ast::Let { bindings, body }) => {
+            eval_expr(*body, Scope::recursive(scope, bindings)?)
+        }
+        ast::ExprKind::Match(ast::Match { scrutinee, arms }) => {
+            let scrutinee_span = scrutinee.span;
+            let value = eval_expr(*scrutinee, scope.clone())?;
+
+            for arm in arms {
+                let mut bindings = HashMap::new();
+                if bind_pattern(&arm.pattern, &value, &scope, &mut bindings)? {
+                    let inner_scope = Scope::from_raw(RawScope {
+                        parent: Some(scope),
+                        bindings,
+                        ..Default::default()
+                    });
+                    return eval_expr(arm.body, inner_scope);
+                }
+            }
+
+            Err(ExprError::NoMatch {
+                value: value.to_string(),
+                span: Some(scrutinee_span),
+            })
+        }
+        ast::ExprKind::Interpolation(parts) => eval_interpolation(parts, scope),
+        ast::ExprKind::Todo => Err(ExprError::Todo { span: Some(span) }),
+        ast::ExprKind::Lambda(ast::Lambda { from, to, .. }) => {
             let parent = scope.clone();
             Ok(Object::new_function(move |argument| {
                 let mut bindings = HashMap::new();
 
-                bindings.insert(from.name.clone(), argument);
+                if !bind_pattern(&from, &argument, &parent, &mut bindings)? {
+                    return Err(ExprError::ArgumentMismatch {
+                        value: argument.to_string(),
+                        span: Some(from.span),
+                    });
+                }
 
                 let inner_scope = Scope::from_raw(RawScope {
                     parent: Some(parent.clone()),
                     bindings,
+                    ..Default::default()
                 });
 
-                eval_expr(*to.clone(), inner_scope)
+                enter_function(|| eval_expr(*to.clone(), inner_scope))
             }))
-        } // _ => todo!(),
+        }
     }
 }
 
-pub fn eval(ast: ast::Ast, scope: Scope) -> Option<Scope> {
-    match ast {
-        ast::Ast::Expr(expr) => {
-            let res = eval_expr(expr, scope.clone());
-            match res {
-                Ok(inner) => info!(?inner),
-                Err(inner) => error!(?inner),
-            }
-            None
+/// How deeply calls of functions defined in the program may nest before
+/// evaluation gives up.
+const MAX_CALL_DEPTH: usize = 10_000;
+
+thread_local! {
+    /// The number of function bodies being evaluated on this thread.
+    static CALL_DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Evaluates the body of a function, growing the stack when it runs low, so
+/// that deep recursion ends with an error rather than a stack overflow.
+fn enter_function(eval: impl FnOnce() -> ExprResult<Object>) -> ExprResult<Object> {
+    let depth = CALL_DEPTH.get();
+    if depth >= MAX_CALL_DEPTH {
+        return Err(ExprError::RecursionLimit {
+            limit: MAX_CALL_DEPTH,
+            span: None,
+        });
+    }
+    CALL_DEPTH.set(depth + 1);
+    let result = stacker::maybe_grow(256 * 1024, 4 * 1024 * 1024, eval);
+    CALL_DEPTH.set(depth);
+    result
+}
+
+/// Copies a record with new fields, which must already exist when `update` is set.
+fn eval_record_copy(
+    record: ast::Expr,
+    fields: Vec<(ast::Identifier, ast::Expr)>,
+    update: bool,
+    scope: Scope,
+) -> ExprResult<Object> {
+    let record_span = record.span;
+    let mut record = eval_expr(record, scope.clone())?
+        .fields()
+        .map_err(|e| e.or_span(record_span))?
+        .clone();
+
+    for (name, value) in fields {
+        if update && !record.contains_key(&name.name) {
+            return Err(missing_field(&name.name, &record).or_span(name.span));
         }
-        ast::Ast::Binding {
-            lhs: ident,
-            rhs: expr,
-        } => {
-            let res = eval_expr(expr, scope.clone());
-            info!("{:#?}", res);
-            let res = res.unwrap();
+        record.insert(name.name, eval_expr(value, scope.clone())?);
+    }
+    Ok(Object::new_product(record))
+}
 
-            let mut new_bindings = scope.0.bindings.clone();
-            new_bindings.insert(ident.name, res);
+/// Whether a literal pattern matches `value`, numbers by their value.
+fn matches_literal(literal: &ast::Literal, value: &RawObject) -> bool {
+    match (literal, value) {
+        (ast::Literal::Integer(a), RawObject::Int(b)) => a == b,
+        (ast::Literal::Integer(a), RawObject::Rational(b)) => b.is_integer() && b.numer() == a,
+        (ast::Literal::Decimal(a), value) => value.rational().as_ref() == Some(&**a),
+        (ast::Literal::String(a), RawObject::String(b)) => a == b,
+        (ast::Literal::Boolean(a), RawObject::Bool(b)) => a == b,
+        _ => false,
+    }
+}
 
-            Some(Scope(Rc::from(RawScope {
-                parent: scope.parent.clone(),
-                bindings: new_bindings,
-            })))
+/// Matches `value` against `pattern`, adding the variables that it binds to
+/// `bindings`. The constructors of the pattern must be in `scope`, so that
+/// misspelled ones are reported instead of never matching.
+fn bind_pattern(
+    pattern: &ast::Pattern,
+    value: &Object,
+    scope: &Scope,
+    bindings: &mut HashMap<String, Object>,
+) -> ExprResult<bool> {
+    match (&pattern.kind, &**value) {
+        (ast::PatternKind::Wildcard, _) => Ok(true),
+        (ast::PatternKind::Variable(name), _) => {
+            bindings.insert(name.name.clone(), value.clone());
+            Ok(true)
+        }
+        (ast::PatternKind::Literal(literal), value) => Ok(matches_literal(literal, value)),
+        (ast::PatternKind::Constructor(name, payload), value) => {
+            if scope.symbol_lookup(&name.name)?.is_none() {
+                return Err(ExprError::UnknownConstructor {
+                    name: name.name.clone(),
+                    span: Some(name.span),
+                });
+            }
+            match (payload, value) {
+                (
+                    None,
+                    RawObject::Variant {
+                        tag, payload: None, ..
+                    },
+                ) => Ok(*tag == name.name),
+                (
+                    Some(pattern),
+                    RawObject::Variant {
+                        tag,
+                        payload: Some(value),
+                        ..
+                    },
+                ) if *tag == name.name => bind_pattern(pattern, value, scope, bindings),
+                _ => Ok(false),
+            }
+        }
+        (ast::PatternKind::Record(patterns), RawObject::Product(fields)) => {
+            for (name, pattern) in patterns {
+                match fields.get(&name.name) {
+                    Some(value) if bind_pattern(pattern, value, scope, bindings)? => {}
+                    _ => return Ok(false),
+                }
+            }
+            Ok(true)
+        }
+        (ast::PatternKind::Record(_), _) => Ok(false),
+        (ast::PatternKind::Tuple(patterns), RawObject::Tuple(elements))
+            if patterns.len() == elements.len() =>
+        {
+            for (pattern, value) in patterns.iter().zip(elements) {
+                if !bind_pattern(pattern, value, scope, bindings)? {
+                    return Ok(false);
+                }
+            }
+            Ok(true)
+        }
+        (ast::PatternKind::Tuple(_), _) => Ok(false),
+        (ast::PatternKind::Annotated(pattern, _), _) => {
+            bind_pattern(pattern, value, scope, bindings)
         }
-        ast::Ast::Todo => todo!(),
     }
 }
 
+/// Declares the types of the program, evaluates its bindings, which can refer
+/// to each other in any order, and then the body if there is one.
+///
+/// Returns the resulting scope together with the value of the body.
+pub fn eval_program(program: ast::Program, scope: Scope) -> ExprResult<(Scope, Option<Object>)> {
+    let scope = match program.types.as_slice() {
+        [] => scope,
+        types => Scope::with_types(scope, types),
+    };
+    let scope = Scope::recursive(scope, program.bindings)?;
+
+    let value = match program.body {
+        Some(body) => Some(eval_expr(body, scope.clone())?),
+        None => None,
+    };
+
+    Ok((scope, value))
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::ast::{FunctionCall, Identifier};
-
     use super::*;
-    use tracing::debug;
+    use crate::complete::parse;
+    use crate::source::Sources;
+    use rstest::rstest;
+    use std::ops::Range;
+    use tracing_test::traced_test;
+
+    fn run(input: &str) -> ExprResult<Option<Object>> {
+        let mut sources = Sources::new();
+        let file = sources.add("test.len", input);
+        let program = parse(&sources, file).unwrap();
+
+        eval_program(program, Scope::std()).map(|(_, value)| value)
+    }
+
+    #[rstest]
+    #[traced_test]
+    #[case("1 + 2", "3")]
+    #[case("a = 1; b = a + 1; b * 10", "20")]
+    #[case("f = x => x + 1\ny = 2\nf y", "3")]
+    #[case("a = 1\nb = a\n  + 1\nb", "2")]
+    #[case("x = 1; { a: x, b: x + 1 }", "{ a: 1, b: 2 }")]
+    #[case("{}", "{}")]
+    #[case("r = { a: { b: 2 } }; r.a.b", "2")]
+    #[case("r = { a: 1 }; inc r.a", "2")]
+    #[case("f = x => { a: x }; (f 3).a", "3")]
+    #[case("1 + 2 * 3", "7")]
+    #[case("if 1 + 1 == 2 then \"yes\" else \"no\"", "\"yes\"")]
+    #[case("if 2 <= 1 then 1 else if \"a\" < \"b\" then 2 else 3", "2")]
+    #[case("{ a: 1, b: { c: true } } == { b: { c: true }, a: 1 }", "true")]
+    #[case("1 != 1", "false")]
+    #[case("if true then 1 else unbound", "1")]
+    #[case("let a = 1; b = a + 1 in a + b", "3")]
+    #[case("x = 1; let x = 2 in x", "2")]
+    #[case("f = n => let double = x => x * 2 in double n + 1\nf 3", "7")]
+    #[case("f = let a = 10 in x => x + a\nf 1", "11")]
+    #[case("(let g = x => x + 1 in g) 1", "2")]
+    #[case("h = let r = { f: x => x * 2 } in r\nh.f 3", "6")]
+    #[case(
+        "fact = n => if n == 0 then 1 else n * fact (n - 1)\nfact 20",
+        "2432902008176640000"
+    )]
+    #[case("b = a + 1; a = 1; b", "2")]
+    #[case("even = n => if n == 0 then true else odd (n - 1)\nodd = n => if n == 0 then false else even (n - 1)\neven 10", "true")]
+    #[case(
+        "let go = n => acc => if n == 0 then acc else go (n - 1) (acc + n) in go 100 0",
+        "5050"
+    )]
+    #[case("r = { name: \"len\", version: 1 }; get r \"name\"", "\"len\"")]
+    #[case("type Option a = Some a | None\nSome (Some 1)", "Some (Some 1)")]
+    #[case(
+        "type Option a = Some a | None\n{ a: None, b: Some { c: 1 } }",
+        "{ a: None, b: Some { c: 1 } }"
+    )]
+    #[case("type Option a = Some a | None\nSome 1 == Some 1", "true")]
+    #[case("type Option a = Some a | None\nSome 1 == None", "false")]
+    #[case("type Result e a = Ok a | Err e\ncheck = n => if n < 0 then Err \"negative\" else Ok n\ncheck (0 - 1)", "Err \"negative\"")]
+    #[case(
+        "type Option a = Some a | None\nmatch Some 2 { None => 0, Some x => x * 10 }",
+        "20"
+    )]
+    #[case("type Option a = Some a | None\nmatch Some (Some 2) { Some None => 0, Some (Some 1) => 1, Some (Some _) => 2, _ => 3 }", "2")]
+    #[case(
+        "match { a: 1, b: \"x\", c: 3 } { { a: 2 } => 0, { a, b: \"x\" } => a, _ => 2 }",
+        "1"
+    )]
+    #[case(
+        "f = n => match n == 0 { true => 1, false => n * f (n - 1) }\nf 5",
+        "120"
+    )]
+    #[case("match \"a\" { \"b\" => 1, s => s }", "\"a\"")]
+    #[case("x = 1; match 2 { x => x }", "2")]
+    #[case("({ a, b } => a + b) { a: 1, b: 2, c: 3 }", "3")]
+    #[case("swap = (a, b) => (b, a)\nswap (1, (2, \"c\"))", "((2, \"c\"), 1)")]
+    #[case("(_ => 1) 2", "1")]
+    #[case("x = 1; (x) == 1 + 0", "true")]
+    #[case("f = { size: (w, h) } => w * h\nf { size: (2, 3) }", "6")]
+    #[case("match (1, 2) { (2, _) => 0, (1, x) => x }", "2")]
+    #[case("add = a b c => a + b + c\nadd 1 2 3", "6")]
+    #[case("add = a b => a + b\ninc = add 1\ninc 2", "3")]
+    #[case(
+        "type Option a = Some a | None\nf = (Some x) { y } _ => x + y\nf (Some 1) { y: 2 } 3",
+        "3"
+    )]
+    #[case("r = { a: 1 }; { b: 2, c: 3 | r }", "{ a: 1, b: 2, c: 3 }")]
+    #[case("r = { a: 1, b: 2 }; { r | b: \"b\" }", "{ a: 1, b: \"b\" }")]
+    #[case(
+        "name = r => r.name; (name { name: 1 }, name { name: 2, age: 3 })",
+        "(1, 2)"
+    )]
+    #[case("f : Int -> Int = (n : Int) => n + 1; f 2 : Int", "3")]
+    #[case("\"foo\" + \"bar\"", "\"foobar\"")]
+    #[case(
+        "Cons (1, Cons (2, Nil)) + Cons (3, Nil)",
+        "Cons (1, Cons (2, Cons (3, Nil)))"
+    )]
+    #[case("Nil + Cons (1, Nil)", "Cons (1, Nil)")]
+    #[case("(1, { a: \"b\" }) == (1, { a: \"b\" })", "true")]
+    #[case("Cons (1, Nil) != Cons (1, Nil)", "false")]
+    #[case("(\"a\" < \"b\", 2 <= 1)", "(true, false)")]
+    #[case("1 + 2.5", "3.5")]
+    #[case("1.5 * 2", "3")]
+    #[case("0.1 + 0.2 == 0.3", "true")]
+    #[case("(1.5, 0.25 * 0.5, 1 - 1.25)", "(1.5, 0.125, -0.25)")]
+    #[case("0xff + 0o7 + 0b1", "263")]
+    #[case("0x1e+1", "31")]
+    #[case("1_000_000 - 1", "999999")]
+    #[case("2.5e3 + 1e-2", "2500.01")]
+    #[case("1 < 1.5", "true")]
+    #[case("match 2.0 { 1 => \"one\", 2 => \"two\", _ => \"other\" }", "\"two\"")]
+    #[case(r#""tab\there" + "\u{21}""#, r#""tab\there!""#)]
+    #[case(r##"r#"C:\dir "quoted""# == "C:\\dir \"quoted\"""##, "true")]
+    #[case(r#"name = "Ada"; "hello ${name}!""#, r#""hello Ada!""#)]
+    #[case(
+        r#""${1 + 2} and ${0.5} ${(true, { a: "b" })} ${Cons (1, Nil)}""#,
+        r#""3 and 0.5 (true, { a: \"b\" }) Cons (1, Nil)""#
+    )]
+    #[case(
+        r#"f = n => "<${if n > 0 then "${n}" else "none"}>"; f 3 + f 0"#,
+        r#""<3><none>""#
+    )]
+    #[case(r#""\${x} costs $5""#, r#""${x} costs $5""#)]
+    #[case(
+        "port = 8080\nconfig = \"\"\"\n    [server]\n      port = ${port}\n    \"\"\"\nconfig",
+        r#""[server]\n  port = 8080\n""#
+    )]
+    #[case("\"\"\"\n  a \"b\" \\\"\"\"\n    c\"\"\"", r#""a \"b\" \"\"\"\n  c""#)]
+    #[case(
+        "## Adds one.\ninc = x => x + 1 # not 2\n#[ inc 0 #[ nested ]# ]#\ninc #[ here ]# 1",
+        "2"
+    )]
+    #[case(
+        "r = {\n  ## The answer.\n  a: 42 ## or so\n}\nmatch r {\n  ## Any.\n  { a } => a,\n}",
+        "42"
+    )]
+    #[case("x = 1\nrational x + 0.5", "1.5")]
+    #[case("inc 1 / 4", "0.5")]
+    #[case("7 / 2", "3.5")]
+    #[case("1 / 3", "1/3")]
+    #[case("x = 6; y = 3; x / y + 0.5", "2.5")]
+    #[case("0.5 / 0.25", "2")]
+    #[case("f = n => if n == 0 then 0 else f (n - 1)\nf 5000", "0")]
+    fn test_program(#[case] input: &str, #[case] expected: &str) {
+        let value = run(input).unwrap().unwrap();
+        assert_eq!(value.to_string(), expected);
+    }
+
+    #[rstest]
+    #[traced_test]
+    #[case("foo + 1", "unbound variable `foo`", 0..3)]
+    #[case("f = 3; f 1", "a value of type Int is not a function", 7..8)]
+    #[case("1 + (x => x)", "`+` can't be applied to Int and Function", 0..12)]
+    #[case("2 $ 1", "a value of type Int is not a function", 0..5)]
+    #[case("inc \"a\"", "expected a value of type Int, found String", 0..7)]
+    #[case("f = x => x * y; f 2", "unbound variable `y`", 13..14)]
+    #[case("{ a: 1, b: c }", "unbound variable `c`", 11..12)]
+    #[case("r = { a: 1, b: 2 }; r.c", "record has no field `c`", 22..23)]
+    #[case("x = 1; x.a", "expected a value of type Record, found Int", 7..8)]
+    #[case("r = { a: 1 }; get r \"b\"", "record has no field `b`", 14..23)]
+    #[case("if 1 then 2 else 3", "expected a value of type Bool, found Int", 3..4)]
+    #[case("1 == \"a\"", "`==` can't be applied to Int and String", 0..8)]
+    #[case("true < false", "`<` can't be applied to Bool and Bool", 0..12)]
+    #[case("(let a = 1 in a) + a", "unbound variable `a`", 19..20)]
+    #[case("a = b; b = a + 1", "`a` depends on its own value", 11..12)]
+    #[case("let x = x in x", "`x` depends on its own value", 8..9)]
+    #[case("type A = A; type B = B\nA == B", "`==` can't be applied to A and B", 23..29)]
+    #[case("match 1 + 1 { 1 => 1, 3 => 3 }", "no pattern matches the value `2`", 6..11)]
+    #[case("match 1 { Sme x => x }", "unknown constructor `Sme`", 10..13)]
+    #[case("f = (a, b) => a; f 1", "the argument `1` doesn't match the parameter of the function", 4..10)]
+    #[case("f = a (b, c) => a; f 1 2", "the argument `2` doesn't match the parameter of the function", 6..12)]
+    #[case("r = { a: 1 }; { r | b: 2 }", "record has no field `b`", 20..21)]
+    #[case("{ a: 1 | 2 }", "expected a value of type Record, found Int", 9..10)]
+    #[case("Cons (1, Nil) + \"a\"", "`+` can't be applied to List and String", 0..19)]
+    #[case("(x => x) == (x => x)", "`==` can't be applied to Function and Function", 0..20)]
+    #[case("1.5 + \"a\"", "`+` can't be applied to Rational and String", 0..9)]
+    #[case("if 0.5 then 1 else 2", "expected a value of type Bool, found Rational", 3..6)]
+    #[case("x = 1; x / (x - 1)", "division by zero", 7..18)]
+    #[case(
+        r#""f is ${x => x}""#,
+        "a value of type Function can't be written into a string",
+        8..14
+    )]
+    #[case(
+        "f = n => if n == 0 then 0 else f (n - 1)\nf 100000",
+        "function calls are nested more than 10000 deep",
+        31..40
+    )]
+    fn test_errors(#[case] input: &str, #[case] message: &str, #[case] span: Range<usize>) {
+        let err = run(input).unwrap_err();
+        assert_eq!(err.to_string(), message);
+        assert_eq!(err.span().map(|s| s.start..s.end), Some(span));
+    }
+
+    #[test]
+    fn test_recursive_scope_is_freed() {
+        let mut sources = Sources::new();
+        let input = "f = n => if n == 0 then 0 else f (n - 1)\ng = { f: f }\ng.f 3";
+        let file = sources.add("test.len", input);
+        let program = parse(&sources, file).unwrap();
+
+        let scope = Scope::recursive(Scope::std(), program.bindings).unwrap();
+        let value = eval_expr(program.body.unwrap(), scope.clone()).unwrap();
+        let group = Rc::downgrade(&scope.0);
+        drop(scope);
+
+        assert_eq!(value.to_string(), "0");
+        assert!(group.upgrade().is_none());
+    }
+
+    #[test]
+    fn test_malformed_prefixed_literal() {
+        let mut sources = Sources::new();
+        let file = sources.add("test.len", "0b1e + 1");
+        let errors = parse(&sources, file).unwrap_err();
+
+        let messages = errors
+            .iter()
+            .map(|e| e.message.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(messages, ["invalid digit `e` in binary literal `0b1e`"]);
+    }
+
+    #[test]
+    fn test_program_without_body() {
+        assert!(run("a = 1; b = 2").unwrap().is_none());
+    }
 }
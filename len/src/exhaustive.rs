@@ -0,0 +1,455 @@
+use crate::ast::{self, Literal, PatternKind};
+use crate::infer::Env;
+use crate::lexer::Span;
+use crate::number::format_rational;
+use crate::Rational;
+use thiserror::Error;
+
+/// Problems with the arms of a `match` that don't stop the program from
+/// running.
+#[derive(Debug, Clone, Error)]
+pub enum PatternWarning {
+    /// Some values, like the `missing` patterns, match none of the arms.
+    #[error("non-exhaustive match")]
+    NonExhaustive { missing: Vec<String>, span: Span },
+    /// The arms above already match every value of the pattern.
+    #[error("unreachable pattern")]
+    Unreachable { span: Span },
+}
+
+impl PatternWarning {
+    pub fn span(&self) -> Span {
+        match self {
+            PatternWarning::NonExhaustive { span, .. } | PatternWarning::Unreachable { span } => {
+                *span
+            }
+        }
+    }
+}
+
+/// Finds the arms of a well-typed `match` that can't be reached, and
+/// examples of the values that no arm matches.
+///
+/// This is the usefulness algorithm of "Warnings for pattern matching" by
+/// Luc Maranget: an arm is reachable if its pattern is useful after the arms
+/// above it, and the match is exhaustive if `_` isn't useful after all of
+/// them.
+pub fn check_match(env: &Env, matching: &ast::Match) -> Vec<PatternWarning> {
+    let analysis = Analysis { env };
+    let mut rows = Vec::new();
+    let mut warnings = Vec::new();
+
+    for arm in &matching.arms {
+        let row = vec![lower(&arm.pattern)];
+        if !analysis.useful(&rows, &row) {
+            warnings.push(PatternWarning::Unreachable {
+                span: arm.pattern.span,
+            });
+        }
+        rows.push(row);
+    }
+
+    let missing = analysis.missing(&rows, 1);
+    if !missing.is_empty() {
+        warnings.insert(
+            0,
+            PatternWarning::NonExhaustive {
+                missing: missing.iter().map(|row| print(&row[0], false)).collect(),
+                span: matching.scrutinee.span,
+            },
+        );
+    }
+
+    warnings
+}
+
+/// A pattern reduced to what matters for matching: which constructors it
+/// tests, and with what patterns for their fields.
+#[derive(Debug, Clone)]
+enum Pat {
+    /// Matches any value, like `_` and variables.
+    Wild,
+    Constructor(Constructor, Vec<Pat>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Constructor {
+    Variant(String),
+    Boolean(bool),
+    /// Integers and decimals by their value, so `1` and `1.0` are the same.
+    Number(Rational),
+    String(String),
+    Tuple(usize),
+    /// A record with these fields, sorted by name. Patterns can leave out
+    /// fields, so the records of a column are all given the fields of every
+    /// one of them before they are compared.
+    Record(Vec<String>),
+}
+
+impl Constructor {
+    /// Whether both constructors match the same values, every record
+    /// pattern being a test for the one constructor of its type.
+    fn same(&self, other: &Constructor) -> bool {
+        match (self, other) {
+            (Constructor::Record(_), Constructor::Record(_)) => true,
+            _ => self == other,
+        }
+    }
+}
+
+fn lower(pattern: &ast::Pattern) -> Pat {
+    match &pattern.kind {
+        PatternKind::Wildcard | PatternKind::Variable(_) => Pat::Wild,
+        PatternKind::Literal(literal) => {
+            let constructor = match literal {
+                Literal::Integer(n) => Constructor::Number(Rational::from_integer(n.clone())),
+                Literal::Decimal(r) => Constructor::Number(*r.clone()),
+                Literal::String(s) => Constructor::String(s.clone()),
+                Literal::Boolean(b) => Constructor::Boolean(*b),
+            };
+            Pat::Constructor(constructor, Vec::new())
+        }
+        PatternKind::Constructor(name, payload) => Pat::Constructor(
+            Constructor::Variant(name.name.clone()),
+            payload.iter().map(|p| lower(p)).collect(),
+        ),
+        PatternKind::Record(fields) => {
+            let mut fields = fields
+                .iter()
+                .map(|(name, p)| (name.name.clone(), lower(p)))
+                .collect::<Vec<_>>();
+            fields.sort_by(|(a, _), (b, _)| a.cmp(b));
+            let (names, patterns) = fields.into_iter().unzip();
+            Pat::Constructor(Constructor::Record(names), patterns)
+        }
+        PatternKind::Tuple(elements) => Pat::Constructor(
+            Constructor::Tuple(elements.len()),
+            elements.iter().map(lower).collect(),
+        ),
+        PatternKind::Annotated(pattern, _) => lower(pattern),
+    }
+}
+
+/// The constructors tested by the first column of a matrix, without
+/// duplicates, the records merged into one with the fields of all of them.
+fn heads<'p>(column: impl Iterator<Item = &'p Pat>) -> Vec<Constructor> {
+    let mut heads: Vec<Constructor> = Vec::new();
+    for pat in column {
+        let Pat::Constructor(constructor, _) = pat else {
+            continue;
+        };
+        match (constructor, heads.iter_mut().find(|h| h.same(constructor))) {
+            (Constructor::Record(fields), Some(Constructor::Record(all))) => {
+                for field in fields {
+                    if !all.contains(field) {
+                        all.push(field.clone());
+                    }
+                }
+                all.sort();
+            }
+            (_, Some(_)) => {}
+            (_, None) => heads.push(constructor.clone()),
+        }
+    }
+    heads
+}
+
+/// The row of patterns that match what's left of the values matched by
+/// `row`, once their first value is known to be built by `constructor`. No
+/// row if `row` can't match such values.
+fn specialize(row: &[Pat], constructor: &Constructor, arity: usize) -> Option<Vec<Pat>> {
+    let (head, rest) = row.split_first()?;
+    let mut specialized = match head {
+        Pat::Wild => vec![Pat::Wild; arity],
+        Pat::Constructor(c, _) if !c.same(constructor) => return None,
+        Pat::Constructor(Constructor::Record(fields), patterns) => {
+            let Constructor::Record(all) = constructor else {
+                unreachable!("records are only the same as records")
+            };
+            all.iter()
+                .map(|field| match fields.iter().position(|f| f == field) {
+                    Some(i) => patterns[i].clone(),
+                    None => Pat::Wild,
+                })
+                .collect()
+        }
+        Pat::Constructor(_, patterns) => patterns.clone(),
+    };
+    specialized.extend_from_slice(rest);
+    Some(specialized)
+}
+
+/// The row of patterns that match what's left of the values matched by
+/// `row`, once their first value is known to be built by none of the
+/// constructors of the column.
+fn default(row: &[Pat]) -> Option<Vec<Pat>> {
+    match row.split_first()? {
+        (Pat::Wild, rest) => Some(rest.to_vec()),
+        (Pat::Constructor(..), _) => None,
+    }
+}
+
+struct Analysis<'a> {
+    env: &'a Env,
+}
+
+impl Analysis<'_> {
+    fn arity(&self, constructor: &Constructor) -> usize {
+        match constructor {
+            Constructor::Variant(name) => self
+                .env
+                .variants(name)
+                .and_then(|variants| variants.into_iter().find(|(n, _)| n == name))
+                .map_or(0, |(_, payload)| usize::from(payload)),
+            Constructor::Boolean(_) | Constructor::Number(_) | Constructor::String(_) => 0,
+            Constructor::Tuple(arity) => *arity,
+            Constructor::Record(fields) => fields.len(),
+        }
+    }
+
+    /// Every constructor of the type of a column whose constructors are
+    /// `heads`, if there are finitely many.
+    fn signature(&self, heads: &[Constructor]) -> Option<Vec<Constructor>> {
+        match heads.first()? {
+            Constructor::Variant(name) => Some(
+                self.env
+                    .variants(name)?
+                    .into_iter()
+                    .map(|(name, _)| Constructor::Variant(name))
+                    .collect(),
+            ),
+            Constructor::Boolean(_) => Some(vec![
+                Constructor::Boolean(true),
+                Constructor::Boolean(false),
+            ]),
+            Constructor::Number(_) | Constructor::String(_) => None,
+            constructor @ (Constructor::Tuple(_) | Constructor::Record(_)) => {
+                Some(vec![constructor.clone()])
+            }
+        }
+    }
+
+    /// The signature of the column, if `heads` covers all of it.
+    fn complete(&self, heads: &[Constructor]) -> Option<Vec<Constructor>> {
+        self.signature(heads)
+            .filter(|all| all.iter().all(|c| heads.iter().any(|h| h.same(c))))
+            .map(|all| {
+                // The records of the heads have the fields of the column
+                all.into_iter()
+                    .map(|c| heads.iter().find(|h| h.same(&c)).cloned().unwrap_or(c))
+                    .collect()
+            })
+    }
+
+    /// Whether `row` matches values that none of the `rows` match.
+    fn useful(&self, rows: &[Vec<Pat>], row: &[Pat]) -> bool {
+        let Some(head) = row.first() else {
+            return rows.is_empty();
+        };
+        let heads = heads(rows.iter().map(|r| &r[0]).chain([head]));
+
+        let specialized = |constructor: &Constructor| {
+            let arity = self.arity(constructor);
+            let rows = rows
+                .iter()
+                .filter_map(|r| specialize(r, constructor, arity))
+                .collect::<Vec<_>>();
+            let row = specialize(row, constructor, arity)
+                .expect("the row matches the constructors of its head");
+            self.useful(&rows, &row)
+        };
+
+        match head {
+            Pat::Constructor(constructor, _) => {
+                let constructor = heads
+                    .iter()
+                    .find(|h| h.same(constructor))
+                    .expect("the head of the row is one of the heads");
+                specialized(constructor)
+            }
+            Pat::Wild => match self.complete(&heads) {
+                Some(all) => all.iter().any(specialized),
+                None => {
+                    let rows = rows.iter().filter_map(|r| default(r)).collect::<Vec<_>>();
+                    self.useful(&rows, &row[1..])
+                }
+            },
+        }
+    }
+
+    /// Rows of `width` patterns for the values that none of the `rows`
+    /// match.
+    fn missing(&self, rows: &[Vec<Pat>], width: usize) -> Vec<Vec<Pat>> {
+        if width == 0 {
+            return if rows.is_empty() {
+                vec![Vec::new()]
+            } else {
+                Vec::new()
+            };
+        }
+        let heads = heads(rows.iter().map(|r| &r[0]));
+
+        if let Some(all) = self.complete(&heads) {
+            let mut missing = Vec::new();
+            for constructor in all {
+                let arity = self.arity(&constructor);
+                let rows = rows
+                    .iter()
+                    .filter_map(|r| specialize(r, &constructor, arity))
+                    .collect::<Vec<_>>();
+                for mut row in self.missing(&rows, arity + width - 1) {
+                    let rest = row.split_off(arity);
+                    let mut unspecialized = vec![Pat::Constructor(constructor.clone(), row)];
+                    unspecialized.extend(rest);
+                    missing.push(unspecialized);
+                }
+            }
+            return missing;
+        }
+
+        let rows = rows.iter().filter_map(|r| default(r)).collect::<Vec<_>>();
+        let rest = self.missing(&rows, width - 1);
+        if rest.is_empty() {
+            return Vec::new();
+        }
+
+        // Name the constructors that no row tests, if they can be listed
+        let firsts = match self.signature(&heads) {
+            Some(all) => all
+                .into_iter()
+                .filter(|c| !heads.iter().any(|h| h.same(c)))
+                .map(|c| {
+                    let arity = self.arity(&c);
+                    Pat::Constructor(c, vec![Pat::Wild; arity])
+                })
+                .collect(),
+            None => vec![Pat::Wild],
+        };
+
+        firsts
+            .iter()
+            .flat_map(|first| {
+                rest.iter().map(move |row| {
+                    let mut missing = vec![first.clone()];
+                    missing.extend_from_slice(row);
+                    missing
+                })
+            })
+            .collect()
+    }
+}
+
+/// Prints a pattern in the syntax of the language. `nested` patterns are the
+/// payload of a constructor, where constructors with a payload need
+/// parentheses.
+fn print(pat: &Pat, nested: bool) -> String {
+    let Pat::Constructor(constructor, patterns) = pat else {
+        return String::from("_");
+    };
+    match constructor {
+        Constructor::Variant(name) => match patterns.as_slice() {
+            [payload] if nested => format!("({name} {})", print(payload, true)),
+            [payload] => format!("{name} {}", print(payload, true)),
+            _ => name.clone(),
+        },
+        Constructor::Boolean(b) => b.to_string(),
+        Constructor::Number(r) => format_rational(r),
+        Constructor::String(s) => format!("{s:?}"),
+        Constructor::Tuple(_) => {
+            let elements = patterns.iter().map(|p| print(p, false)).collect::<Vec<_>>();
+            format!("({})", elements.join(", "))
+        }
+        Constructor::Record(fields) => {
+            // Fields left out of a record pattern match anything
+            let fields = fields
+                .iter()
+                .zip(patterns)
+                .filter(|(_, p)| !matches!(p, Pat::Wild))
+                .map(|(name, p)| format!("{name}: {}", print(p, false)))
+                .collect::<Vec<_>>();
+            if fields.is_empty() {
+                String::from("_")
+            } else {
+                format!("{{ {} }}", fields.join(", "))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::complete::parse;
+    use crate::infer::check_program;
+    use crate::source::Sources;
+    use rstest::rstest;
+
+    const TYPES: &str = "type Option a = Some a | None\n\
+                         type Result a e = Ok a | Err e\n\
+                         type Color = Red | Green | Blue\n";
+
+    fn warnings(input: &str) -> Vec<String> {
+        let mut sources = Sources::new();
+        let file = sources.add("main.len", format!("{TYPES}{input}"));
+        let program = parse(&sources, file).expect("the program parses");
+        let checked = check_program(&program, &Env::std()).expect("the program type checks");
+
+        checked
+            .warnings
+            .iter()
+            .map(|warning| {
+                let span = warning.span();
+                let span = span.start - TYPES.len()..span.end - TYPES.len();
+                match warning {
+                    PatternWarning::NonExhaustive { missing, .. } => {
+                        format!("{warning} {span:?}: {}", missing.join(", "))
+                    }
+                    PatternWarning::Unreachable { .. } => format!("{warning} {span:?}"),
+                }
+            })
+            .collect()
+    }
+
+    #[rstest]
+    #[case("match Some 1 { Some x => x, None => 0 }", &[])]
+    #[case("match Some 1 { None => 0, _ => 1 }", &[])]
+    #[case("match Some 1 { None => 0 }", &["non-exhaustive match 6..12: Some _"])]
+    #[case("match Red { Green => 0 }", &["non-exhaustive match 6..9: Red, Blue"])]
+    #[case(
+        "match Some (Ok 1) { Some (Ok n) => n, None => 0 }",
+        &["non-exhaustive match 6..17: Some (Err _)"]
+    )]
+    #[case("match true { true => 1, false => 0 }", &[])]
+    #[case("match true { true => 1 }", &["non-exhaustive match 6..10: false"])]
+    #[case("match 1 { 0 => 1, 1 => 2 }", &["non-exhaustive match 6..7: _"])]
+    #[case("match \"a\" { \"a\" => 1, s => 2 }", &[])]
+    #[case(
+        "match (true, false) { (true, _) => 1, (_, true) => 2 }",
+        &["non-exhaustive match 6..19: (false, false)"]
+    )]
+    #[case(
+        "match { a: true, b: 1 } { { a: true } => 1, { b: 1 } => 2 }",
+        &["non-exhaustive match 6..23: { a: false }"]
+    )]
+    #[case("match { a: true } { { a: true } => 1, { a: false } => 0 }", &[])]
+    #[case("match Some 1 { _ => 0, None => 1 }", &["unreachable pattern 23..27"])]
+    #[case(
+        "match Some 1 { Some _ => 0, Some 1 => 1, None => 2 }",
+        &["unreachable pattern 28..34"]
+    )]
+    #[case(
+        "match Red { Red => 0, Red => 1 }",
+        &["non-exhaustive match 6..9: Green, Blue", "unreachable pattern 22..25"]
+    )]
+    #[case(
+        "match true { (b : Bool) => 1, false => 0 }",
+        &["unreachable pattern 30..35"]
+    )]
+    #[case(
+        "f = o => match o { Some x => x }\nf None",
+        &["non-exhaustive match 15..16: None"]
+    )]
+    fn test_warnings(#[case] input: &str, #[case] expected: &[&str]) {
+        assert_eq!(warnings(input), expected);
+    }
+}
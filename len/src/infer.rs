@@ -0,0 +1,1778 @@
+use crate::ast::{self, is_constructor, ExprKind, PatternKind, TypeExprKind};
+use crate::class::{Class, Head, Instance, LIST};
+use crate::exhaustive::{check_match, PatternWarning};
+use crate::lexer::{lexer, Span, SpanContext};
+use crate::ty::{Primitive, Scheme, Ty, TyPrinter, TyVar};
+use chumsky::{error::Rich, extra, input::Input, Parser};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use thiserror::Error;
+
+/// Types of the built-in values of [`crate::eval::Scope::std`].
+const STD: &[(&str, &str)] = &[
+    ("+", "Add a => a -> a -> a"),
+    ("-", "Num a => a -> a -> a"),
+    ("*", "Num a => a -> a -> a"),
+    ("/", "Num a => a -> a -> Rational"),
+    ("==", "Eq a => a -> a -> Bool"),
+    ("!=", "Eq a => a -> a -> Bool"),
+    ("<", "Ord a => a -> a -> Bool"),
+    ("<=", "Ord a => a -> a -> Bool"),
+    (">", "Ord a => a -> a -> Bool"),
+    (">=", "Ord a => a -> a -> Bool"),
+    ("$", "(a -> b) -> a -> b"),
+    ("inc", "Int -> Int"),
+    ("rational", "Num a => a -> Rational"),
+];
+
+/// The built-in that reads a field by name. Its type depends on the name, so
+/// it has no scheme in [`STD`], and the checker only accepts it applied to a
+/// record and a string literal.
+const GET: &str = "get";
+
+/// Errors found by the type checker, before the program runs.
+#[derive(Debug, Clone, Error)]
+pub enum TypeError {
+    #[error("mismatched types")]
+    Mismatch {
+        expected: String,
+        found: String,
+        span: Span,
+    },
+    #[error("cannot construct the infinite type `{variable} = {ty}`")]
+    InfiniteType {
+        variable: String,
+        ty: String,
+        span: Span,
+    },
+    #[error("unbound variable `{name}`")]
+    UnboundVariable { name: String, span: Span },
+    #[error("unknown constructor `{name}`")]
+    UnknownConstructor { name: String, span: Span },
+    #[error("constructor `{name}` {}", if *expected { "takes a value" } else { "doesn't take a value" })]
+    ConstructorPayload {
+        name: String,
+        expected: bool,
+        span: Span,
+    },
+    #[error("expected a function, found `{found}`")]
+    NotAFunction { found: String, span: Span },
+    #[error("expected a record, found `{found}`")]
+    NotARecord { found: String, span: Span },
+    #[error("type `{record}` has no field `{field}`")]
+    MissingField {
+        record: String,
+        field: String,
+        available: Vec<String>,
+        span: Span,
+    },
+    #[error("the record already has a field `{field}`")]
+    ExistingField { field: String, span: Span },
+    #[error("unknown type `{name}`")]
+    UnknownType { name: String, span: Span },
+    #[error("type `{name}` expects {expected} arguments, found {found}")]
+    TypeArity {
+        name: String,
+        expected: usize,
+        found: usize,
+        span: Span,
+    },
+    #[error("unbound type variable `{name}`")]
+    UnboundTypeVariable { name: String, span: Span },
+    #[error("unknown class `{name}`")]
+    UnknownClass { name: String, span: Span },
+    #[error("type `{ty}` is not an instance of `{class}`")]
+    NoInstance {
+        class: Class,
+        ty: String,
+        /// Whether the type is a variable of an annotation, which is only
+        /// in the classes that the annotation gives it.
+        variable: bool,
+        span: Span,
+    },
+    #[error("type `{name}` is built in, and can't be declared again")]
+    BuiltinType { name: String, span: Span },
+    #[error("`get` needs a record and the name of its field as a string literal")]
+    FieldName { span: Span },
+}
+
+impl TypeError {
+    pub fn span(&self) -> Span {
+        match self {
+            TypeError::Mismatch { span, .. }
+            | TypeError::InfiniteType { span, .. }
+            | TypeError::UnboundVariable { span, .. }
+            | TypeError::UnknownConstructor { span, .. }
+            | TypeError::ConstructorPayload { span, .. }
+            | TypeError::NotAFunction { span, .. }
+            | TypeError::NotARecord { span, .. }
+            | TypeError::MissingField { span, .. }
+            | TypeError::ExistingField { span, .. }
+            | TypeError::UnknownType { span, .. }
+            | TypeError::TypeArity { span, .. }
+            | TypeError::UnboundTypeVariable { span, .. }
+            | TypeError::UnknownClass { span, .. }
+            | TypeError::NoInstance { span, .. }
+            | TypeError::BuiltinType { span, .. }
+            | TypeError::FieldName { span } => *span,
+        }
+    }
+}
+
+pub type TypeResult<T> = Result<T, TypeError>;
+
+/// A sum type declared with `type`.
+#[derive(Debug, Clone)]
+pub struct TypeInfo {
+    pub parameters: usize,
+    /// Names of the constructors, in declaration order.
+    pub constructors: Vec<String>,
+}
+
+/// A constructor of `type_name`, generic over the parameters of the type.
+#[derive(Debug, Clone)]
+struct Constructor {
+    type_name: String,
+    parameters: Vec<TyVar>,
+    payload: Option<Ty>,
+}
+
+/// The types of everything in scope, the counterpart of
+/// [`crate::eval::Scope`] for the type checker.
+#[derive(Debug, Clone, Default)]
+pub struct Env {
+    values: HashMap<String, Scheme>,
+    types: HashMap<String, TypeInfo>,
+    constructors: HashMap<String, Constructor>,
+}
+
+impl Env {
+    pub fn std() -> Self {
+        let mut env = Self::default();
+        env.declare(&crate::prelude::types())
+            .expect("the types of the prelude are valid");
+        for (name, ty) in STD {
+            let scheme = env.parse_scheme(ty);
+            env.values.insert(name.to_string(), scheme);
+        }
+        env
+    }
+
+    pub fn value(&self, name: &str) -> Option<&Scheme> {
+        self.values.get(name)
+    }
+
+    pub fn type_info(&self, name: &str) -> Option<&TypeInfo> {
+        self.types.get(name)
+    }
+
+    /// The constructors of the type that `constructor` belongs to, in the
+    /// order of its declaration, with whether each of them holds a value.
+    pub fn variants(&self, constructor: &str) -> Option<Vec<(String, bool)>> {
+        let info = self
+            .types
+            .get(&self.constructors.get(constructor)?.type_name)?;
+        Some(
+            info.constructors
+                .iter()
+                .map(|name| (name.clone(), self.constructors[name].payload.is_some()))
+                .collect(),
+        )
+    }
+
+    /// A type written in the syntax of the language, whose variables are
+    /// all generic.
+    fn parse_scheme(&self, source: &str) -> Scheme {
+        let context = SpanContext::default();
+        let tokens = lexer::<extra::Err<Rich<_, _>>>()
+            .parse(source.with_context(context))
+            .into_result()
+            .expect("built-in types are valid tokens");
+        let eoi = context.span(source.len()..source.len());
+        let expr = ast::annotation_parser::<extra::Err<Rich<_, _>>>()
+            .parse(tokens.as_slice().spanned(eoi))
+            .into_result()
+            .expect("built-in types are valid types");
+
+        let variables = type_variables(&expr)
+            .into_iter()
+            .enumerate()
+            .map(|(i, name)| (name, Ty::Var(TyVar(i as u32))))
+            .collect::<HashMap<_, _>>();
+        let (classes, expr) =
+            constraints(&expr, &variables).expect("built-in types only use known classes");
+        let ty = self
+            .convert(expr, &variables)
+            .expect("built-in types only refer to known types");
+
+        Scheme {
+            variables: ty.variables(),
+            lacks: BTreeMap::new(),
+            classes,
+            ty,
+        }
+    }
+
+    /// Adds the types and their constructors, which can refer to each other
+    /// in any order.
+    fn declare(&mut self, decls: &[ast::TypeDecl]) -> TypeResult<()> {
+        for decl in decls {
+            // The instances of the list of the prelude are built in
+            if decl.name.name == LIST && self.types.contains_key(LIST) {
+                return Err(TypeError::BuiltinType {
+                    name: decl.name.name.clone(),
+                    span: decl.name.span,
+                });
+            }
+            self.types.insert(
+                decl.name.name.clone(),
+                TypeInfo {
+                    parameters: decl.parameters.len(),
+                    constructors: decl.variants.iter().map(|v| v.name.name.clone()).collect(),
+                },
+            );
+        }
+
+        for decl in decls {
+            let parameters = (0..decl.parameters.len() as u32)
+                .map(TyVar)
+                .collect::<Vec<_>>();
+            let variables = decl
+                .parameters
+                .iter()
+                .zip(&parameters)
+                .map(|(name, var)| (name.name.clone(), Ty::Var(*var)))
+                .collect::<HashMap<_, _>>();
+            let result = Ty::Sum {
+                name: decl.name.name.clone(),
+                arguments: parameters.iter().copied().map(Ty::Var).collect(),
+            };
+
+            for variant in &decl.variants {
+                let payload = match &variant.payload {
+                    Some(payload) => Some(self.convert(payload, &variables)?),
+                    None => None,
+                };
+                let ty = match &payload {
+                    Some(payload) => Ty::function(payload.clone(), result.clone()),
+                    None => result.clone(),
+                };
+
+                self.values.insert(
+                    variant.name.name.clone(),
+                    Scheme {
+                        variables: parameters.clone(),
+                        lacks: BTreeMap::new(),
+                        classes: BTreeMap::new(),
+                        ty,
+                    },
+                );
+                self.constructors.insert(
+                    variant.name.name.clone(),
+                    Constructor {
+                        type_name: decl.name.name.clone(),
+                        parameters: parameters.clone(),
+                        payload,
+                    },
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The type denoted by a type expression, where `variables` gives the
+    /// type of each variable it may use.
+    fn convert(&self, expr: &ast::TypeExpr, variables: &HashMap<String, Ty>) -> TypeResult<Ty> {
+        match &expr.kind {
+            TypeExprKind::Variable(name) => {
+                variables
+                    .get(&name.name)
+                    .cloned()
+                    .ok_or_else(|| TypeError::UnboundTypeVariable {
+                        name: name.name.clone(),
+                        span: name.span,
+                    })
+            }
+            TypeExprKind::Named(name, arguments) => {
+                let expected = match Primitive::from_name(&name.name) {
+                    Some(_) => 0,
+                    None => match self.types.get(&name.name) {
+                        Some(info) => info.parameters,
+                        None => {
+                            return Err(TypeError::UnknownType {
+                                name: name.name.clone(),
+                                span: name.span,
+                            })
+                        }
+                    },
+                };
+                if arguments.len() != expected {
+                    return Err(TypeError::TypeArity {
+                        name: name.name.clone(),
+                        expected,
+                        found: arguments.len(),
+                        span: expr.span,
+                    });
+                }
+
+                Ok(match Primitive::from_name(&name.name) {
+                    Some(primitive) => Ty::Primitive(primitive),
+                    None => Ty::Sum {
+                        name: name.name.clone(),
+                        arguments: arguments
+                            .iter()
+                            .map(|argument| self.convert(argument, variables))
+                            .collect::<TypeResult<_>>()?,
+                    },
+                })
+            }
+            TypeExprKind::Record { fields, rest } => Ok(Ty::Product {
+                fields: fields
+                    .iter()
+                    .map(|(name, ty)| Ok((name.name.clone(), self.convert(ty, variables)?)))
+                    .collect::<TypeResult<_>>()?,
+                rest: match rest {
+                    Some(name) => match variables.get(&name.name) {
+                        Some(Ty::Var(var)) => Some(*var),
+                        _ => {
+                            return Err(TypeError::UnboundTypeVariable {
+                                name: name.name.clone(),
+                                span: name.span,
+                            })
+                        }
+                    },
+                    None => None,
+                },
+            }),
+            TypeExprKind::Function(from, to) => Ok(Ty::function(
+                self.convert(from, variables)?,
+                self.convert(to, variables)?,
+            )),
+            TypeExprKind::Tuple(elements) => Ok(Ty::Tuple(
+                elements
+                    .iter()
+                    .map(|element| self.convert(element, variables))
+                    .collect::<TypeResult<_>>()?,
+            )),
+            TypeExprKind::Constrained { .. } => {
+                unreachable!("constraints only start annotations, which split them off first")
+            }
+        }
+    }
+}
+
+/// Splits the constraints off the start of an annotation, into the classes
+/// of the variables that they are about.
+fn constraints<'e>(
+    expr: &'e ast::TypeExpr,
+    variables: &HashMap<String, Ty>,
+) -> TypeResult<(BTreeMap<TyVar, BTreeSet<Class>>, &'e ast::TypeExpr)> {
+    let TypeExprKind::Constrained { constraints, ty } = &expr.kind else {
+        return Ok((BTreeMap::new(), expr));
+    };
+
+    let mut classes = BTreeMap::<_, BTreeSet<_>>::new();
+    for constraint in constraints {
+        let class =
+            Class::from_name(&constraint.class.name).ok_or_else(|| TypeError::UnknownClass {
+                name: constraint.class.name.clone(),
+                span: constraint.class.span,
+            })?;
+        let Some(Ty::Var(var)) = variables.get(&constraint.variable.name) else {
+            return Err(TypeError::UnboundTypeVariable {
+                name: constraint.variable.name.clone(),
+                span: constraint.variable.span,
+            });
+        };
+        classes.entry(*var).or_default().insert(class);
+    }
+
+    Ok((classes, ty))
+}
+
+/// The names of the variables of a type expression, in order of appearance.
+fn type_variables(expr: &ast::TypeExpr) -> Vec<String> {
+    fn collect(expr: &ast::TypeExpr, names: &mut Vec<String>) {
+        match &expr.kind {
+            TypeExprKind::Variable(name) => {
+                if !names.contains(&name.name) {
+                    names.push(name.name.clone());
+                }
+            }
+            TypeExprKind::Named(_, arguments) => arguments.iter().for_each(|a| collect(a, names)),
+            TypeExprKind::Record { fields, rest } => {
+                fields.iter().for_each(|(_, ty)| collect(ty, names));
+                if let Some(rest) = rest {
+                    if !names.contains(&rest.name) {
+                        names.push(rest.name.clone());
+                    }
+                }
+            }
+            TypeExprKind::Function(from, to) => {
+                collect(from, names);
+                collect(to, names);
+            }
+            TypeExprKind::Tuple(elements) => elements.iter().for_each(|e| collect(e, names)),
+            TypeExprKind::Constrained { ty, .. } => collect(ty, names),
+        }
+    }
+
+    let mut names = Vec::new();
+    collect(expr, &mut names);
+    names
+}
+
+/// The outcome of checking a program.
+#[derive(Debug, Clone)]
+pub struct Checked {
+    /// The environment the program was checked in, with its types and
+    /// bindings added.
+    pub env: Env,
+    /// The types of the bindings, in source order.
+    pub bindings: Vec<(String, Scheme)>,
+    pub body: Option<Ty>,
+    /// Problems with the matches of the program, in the order in which they
+    /// were checked.
+    pub warnings: Vec<PatternWarning>,
+}
+
+/// Infers the types of a program, on top of the bindings of `env`.
+pub fn check_program(program: &ast::Program, env: &Env) -> TypeResult<Checked> {
+    let mut env = env.clone();
+    env.declare(&program.types)?;
+
+    let mut checker = Checker::new(&env);
+    checker.infer_bindings(&program.bindings)?;
+    let body = match &program.body {
+        Some(body) => {
+            let ty = checker.infer(body)?;
+            checker.default_numbers(&ty);
+            Some(checker.resolve(&ty))
+        }
+        None => None,
+    };
+
+    let bindings = program
+        .bindings
+        .iter()
+        .map(|binding| {
+            let (_, scheme) = checker
+                .locals
+                .iter()
+                .rev()
+                .find(|(name, _)| *name == binding.lhs.name)
+                .expect("every binding of the program is in scope");
+            (binding.lhs.name.clone(), scheme.clone())
+        })
+        .collect::<Vec<_>>();
+    let warnings = checker.warnings;
+
+    for (name, scheme) in &bindings {
+        env.values.insert(name.clone(), scheme.clone());
+    }
+
+    Ok(Checked {
+        env,
+        bindings,
+        body,
+        warnings,
+    })
+}
+
+/// Why two types couldn't be unified, the types being the innermost parts
+/// that differ.
+enum UnifyError {
+    Mismatch,
+    Occurs(TyVar, Ty),
+    /// A row got a field that it must lack.
+    Lacks(String),
+    /// A type isn't in a class that it must be in.
+    Instance(Class, Ty),
+}
+
+/// Algorithm W, with the substitution kept in a table indexed by type
+/// variable.
+struct Checker<'a> {
+    env: &'a Env,
+    substitution: Vec<Option<Ty>>,
+    /// Bindings introduced by the expression being checked, the innermost
+    /// last.
+    locals: Vec<(String, Scheme)>,
+    /// Fields that the records of a row variable can't have.
+    lacks: HashMap<TyVar, BTreeSet<String>>,
+    /// Variables of type annotations that stand for any type, so they only
+    /// unify with themselves and with flexible variables.
+    rigid: HashSet<TyVar>,
+    /// The classes that the types of a variable must be in. Those of a row
+    /// variable are for the types of all the fields of the row.
+    classes: HashMap<TyVar, BTreeSet<Class>>,
+    warnings: Vec<PatternWarning>,
+}
+
+impl<'a> Checker<'a> {
+    fn new(env: &'a Env) -> Self {
+        Self {
+            env,
+            substitution: Vec::new(),
+            locals: Vec::new(),
+            lacks: HashMap::new(),
+            rigid: HashSet::new(),
+            classes: HashMap::new(),
+            warnings: Vec::new(),
+        }
+    }
+
+    fn fresh(&mut self) -> Ty {
+        Ty::Var(self.fresh_var())
+    }
+
+    fn fresh_var(&mut self) -> TyVar {
+        self.substitution.push(None);
+        TyVar(self.substitution.len() as u32 - 1)
+    }
+
+    fn binding(&self, var: TyVar) -> Option<&Ty> {
+        self.substitution.get(var.0 as usize)?.as_ref()
+    }
+
+    /// Follows the substitution until the outermost constructor of the type,
+    /// and until the end of the row of records.
+    fn shallow(&self, ty: &Ty) -> Ty {
+        let mut ty = ty.clone();
+        while let Ty::Var(var) = ty {
+            match self.binding(var) {
+                Some(bound) => ty = bound.clone(),
+                None => break,
+            }
+        }
+
+        if let Ty::Product { fields, rest } = &mut ty {
+            while let Some(Ty::Product {
+                fields: more,
+                rest: next,
+            }) = rest.and_then(|rest| self.binding(rest))
+            {
+                fields.extend(more.clone());
+                *rest = *next;
+            }
+        }
+        ty
+    }
+
+    /// Applies the substitution everywhere in the type.
+    fn resolve(&self, ty: &Ty) -> Ty {
+        match self.shallow(ty) {
+            ty @ (Ty::Var(_) | Ty::Primitive(_)) => ty,
+            Ty::Function(from, to) => Ty::function(self.resolve(&from), self.resolve(&to)),
+            Ty::Tuple(elements) => Ty::Tuple(elements.iter().map(|t| self.resolve(t)).collect()),
+            Ty::Product { fields, rest } => Ty::Product {
+                fields: fields
+                    .iter()
+                    .map(|(name, t)| (name.clone(), self.resolve(t)))
+                    .collect(),
+                rest,
+            },
+            Ty::Sum { name, arguments } => Ty::Sum {
+                name,
+                arguments: arguments.iter().map(|t| self.resolve(t)).collect(),
+            },
+        }
+    }
+
+    fn unify(&mut self, a: &Ty, b: &Ty) -> Result<(), UnifyError> {
+        match (self.shallow(a), self.shallow(b)) {
+            (Ty::Var(a), Ty::Var(b)) if a == b => Ok(()),
+            (Ty::Var(var), ty) | (ty, Ty::Var(var)) if !self.rigid.contains(&var) => {
+                // A number that would be anything else is just the wrong type
+                if self.is_number(var)
+                    && head(&ty).is_some_and(|head| Class::Num.instance(head).is_none())
+                {
+                    return Err(UnifyError::Mismatch);
+                }
+                let ty = self.resolve(&ty);
+                if ty.variables().contains(&var) {
+                    return Err(UnifyError::Occurs(var, ty));
+                }
+                self.substitution[var.0 as usize] = Some(ty.clone());
+                for class in self.classes.remove(&var).unwrap_or_default() {
+                    self.require(class, &ty)?;
+                }
+                Ok(())
+            }
+            (Ty::Primitive(a), Ty::Primitive(b)) if a == b => Ok(()),
+            (Ty::Function(a_from, a_to), Ty::Function(b_from, b_to)) => {
+                self.unify(&a_from, &b_from)?;
+                self.unify(&a_to, &b_to)
+            }
+            (Ty::Tuple(a), Ty::Tuple(b)) if a.len() == b.len() => {
+                a.iter().zip(&b).try_for_each(|(a, b)| self.unify(a, b))
+            }
+            (
+                Ty::Product { fields, rest },
+                Ty::Product {
+                    fields: b_fields,
+                    rest: b_rest,
+                },
+            ) => {
+                for (name, ty) in &fields {
+                    if let Some(b_ty) = b_fields.get(name) {
+                        self.unify(ty, b_ty)?;
+                    }
+                }
+
+                // Each row gets the fields that only the other one has
+                let only = |a: &BTreeMap<String, Ty>, b: &BTreeMap<String, Ty>| {
+                    a.iter()
+                        .filter(|(name, _)| !b.contains_key(*name))
+                        .map(|(name, ty)| (name.clone(), ty.clone()))
+                        .collect::<BTreeMap<_, _>>()
+                };
+                let (only_a, only_b) = (only(&fields, &b_fields), only(&b_fields, &fields));
+
+                match (rest, b_rest) {
+                    (None, None) | (Some(_), Some(_))
+                        if only_a.is_empty() && only_b.is_empty() && rest == b_rest =>
+                    {
+                        Ok(())
+                    }
+                    (Some(rest), None) if only_a.is_empty() => self.bind_row(rest, only_b, None),
+                    (None, Some(b_rest)) if only_b.is_empty() => {
+                        self.bind_row(b_rest, only_a, None)
+                    }
+                    // A rigid row can't get more fields, but it can be the
+                    // end of a flexible one
+                    (Some(rest), Some(b_rest))
+                        if self.rigid.contains(&rest) && only_b.is_empty() =>
+                    {
+                        self.bind_row(b_rest, only_a, Some(rest))
+                    }
+                    (Some(rest), Some(b_rest))
+                        if self.rigid.contains(&b_rest) && only_a.is_empty() =>
+                    {
+                        self.bind_row(rest, only_b, Some(b_rest))
+                    }
+                    (Some(rest), Some(b_rest)) if rest != b_rest => {
+                        let common = self.fresh_var();
+                        self.bind_row(rest, only_b, Some(common))?;
+                        self.bind_row(b_rest, only_a, Some(common))
+                    }
+                    _ => Err(UnifyError::Mismatch),
+                }
+            }
+            (
+                Ty::Sum { name, arguments },
+                Ty::Sum {
+                    name: b_name,
+                    arguments: b_arguments,
+                },
+            ) if name == b_name => arguments
+                .iter()
+                .zip(&b_arguments)
+                .try_for_each(|(a, b)| self.unify(a, b)),
+            _ => Err(UnifyError::Mismatch),
+        }
+    }
+
+    /// Binds the row variable `var` to more `fields`, and maybe a row of
+    /// others.
+    fn bind_row(
+        &mut self,
+        var: TyVar,
+        fields: BTreeMap<String, Ty>,
+        rest: Option<TyVar>,
+    ) -> Result<(), UnifyError> {
+        if self.rigid.contains(&var) {
+            return Err(UnifyError::Mismatch);
+        }
+        let lacks = self.lacks.remove(&var).unwrap_or_default();
+        if let Some(field) = fields.keys().find(|field| lacks.contains(*field)) {
+            return Err(UnifyError::Lacks(field.clone()));
+        }
+
+        let ty = self.resolve(&Ty::Product { fields, rest });
+        if ty.variables().contains(&var) {
+            return Err(UnifyError::Occurs(var, ty));
+        }
+
+        if let Some(rest) = rest {
+            self.lacks.entry(rest).or_default().extend(lacks);
+        }
+        self.substitution[var.0 as usize] = Some(ty.clone());
+        for class in self.classes.remove(&var).unwrap_or_default() {
+            self.require(class, &ty)?;
+        }
+        Ok(())
+    }
+
+    /// Checks that `ty` is in `class`, or will be once its variables are
+    /// known.
+    fn require(&mut self, class: Class, ty: &Ty) -> Result<(), UnifyError> {
+        self.require_in(class, ty, &mut Vec::new())
+    }
+
+    /// `sums` are the sum types whose constructors are being checked, which
+    /// are assumed to be in the class when they contain themselves.
+    fn require_in(
+        &mut self,
+        class: Class,
+        ty: &Ty,
+        sums: &mut Vec<String>,
+    ) -> Result<(), UnifyError> {
+        let ty = self.shallow(ty);
+        let Some(head) = head(&ty) else {
+            let Ty::Var(var) = &ty else {
+                unreachable!("only variables have no head")
+            };
+            let classes = self.classes.entry(*var).or_default();
+            if classes
+                .iter()
+                .any(|c| *c == class || c.superclasses().contains(&class))
+            {
+                return Ok(());
+            }
+            if self.rigid.contains(var) {
+                return Err(UnifyError::Instance(class, ty));
+            }
+            classes.retain(|c| !class.superclasses().contains(c));
+            classes.insert(class);
+            return Ok(());
+        };
+
+        match class.instance(head) {
+            None => Err(UnifyError::Instance(class, self.resolve(&ty))),
+            Some(Instance::StructuralEquality | Instance::StructuralDisplay) => match &ty {
+                Ty::Tuple(elements) => elements
+                    .iter()
+                    .try_for_each(|element| self.require_in(class, element, sums)),
+                Ty::Product { fields, rest } => {
+                    for field in fields.values() {
+                        self.require_in(class, field, sums)?;
+                    }
+                    match rest {
+                        Some(rest) => self.require_in(class, &Ty::Var(*rest), sums),
+                        None => Ok(()),
+                    }
+                }
+                Ty::Sum { name, .. } if sums.contains(name) => Ok(()),
+                Ty::Sum { name, arguments } => {
+                    sums.push(name.clone());
+                    let payloads = self
+                        .env
+                        .types
+                        .get(name)
+                        .map(|info| {
+                            info.constructors
+                                .iter()
+                                .filter_map(|c| {
+                                    let constructor = &self.env.constructors[c];
+                                    let mapping = constructor
+                                        .parameters
+                                        .iter()
+                                        .copied()
+                                        .zip(arguments.iter().cloned())
+                                        .collect::<HashMap<_, _>>();
+                                    Some(constructor.payload.as_ref()?.substitute(&mapping))
+                                })
+                                .collect::<Vec<_>>()
+                        })
+                        .unwrap_or_default();
+                    for payload in &payloads {
+                        self.require_in(class, payload, sums)?;
+                    }
+                    sums.pop();
+                    Ok(())
+                }
+                _ => Ok(()),
+            },
+            Some(_) => Ok(()),
+        }
+    }
+
+    /// Whether `var` is the type of a number that isn't known yet.
+    fn is_number(&self, var: TyVar) -> bool {
+        !self.rigid.contains(&var)
+            && self
+                .classes
+                .get(&var)
+                .is_some_and(|classes| classes.contains(&Class::Num))
+    }
+
+    /// Makes integers the numbers in `ty` whose type is still unknown, so
+    /// that the program `1 + 2` is an `Int` rather than any `Num a => a`.
+    /// `Int` is in every class that a number can be required to be in.
+    fn default_numbers(&mut self, ty: &Ty) {
+        for var in self.resolve(ty).variables() {
+            if self.is_number(var) {
+                self.classes.remove(&var);
+                self.substitution[var.0 as usize] = Some(Ty::Primitive(Primitive::Int));
+            }
+        }
+    }
+
+    /// The outermost constructor of a type that can't be a number, which is
+    /// an `Int` if it turns out to be one, so that errors can say so.
+    fn shallow_non_number(&mut self, ty: &Ty) -> Ty {
+        match self.shallow(ty) {
+            Ty::Var(var) if self.is_number(var) => {
+                self.default_numbers(&Ty::Var(var));
+                self.shallow(ty)
+            }
+            ty => ty,
+        }
+    }
+
+    /// The type of a literal. Integers can be any number, so that `1 + 0.5`
+    /// is a `Rational`.
+    fn literal(&mut self, literal: &ast::Literal) -> Ty {
+        let primitive = match literal {
+            ast::Literal::Integer(_) => {
+                let var = self.fresh_var();
+                self.classes.insert(var, BTreeSet::from([Class::Num]));
+                return Ty::Var(var);
+            }
+            ast::Literal::Decimal(_) => Primitive::Rational,
+            ast::Literal::String(_) => Primitive::String,
+            ast::Literal::Boolean(_) => Primitive::Bool,
+        };
+        Ty::Primitive(primitive)
+    }
+
+    /// Unifies the type that the context of `span` expects with the one that
+    /// was found there.
+    fn expect(&mut self, expected: &Ty, found: &Ty, span: Span) -> TypeResult<()> {
+        self.unify(expected, found).map_err(|err| {
+            let mut printer = TyPrinter::new();
+            match err {
+                UnifyError::Mismatch => {
+                    self.default_numbers(expected);
+                    self.default_numbers(found);
+                    TypeError::Mismatch {
+                        expected: printer.print(&self.resolve(expected)),
+                        found: printer.print(&self.resolve(found)),
+                        span,
+                    }
+                }
+                UnifyError::Occurs(var, ty) => TypeError::InfiniteType {
+                    variable: printer.print(&Ty::Var(var)),
+                    ty: printer.print(&ty),
+                    span,
+                },
+                UnifyError::Lacks(field) => TypeError::ExistingField { field, span },
+                UnifyError::Instance(class, ty) => TypeError::NoInstance {
+                    class,
+                    ty: printer.print(&ty),
+                    variable: matches!(ty, Ty::Var(_)),
+                    span,
+                },
+            }
+        })
+    }
+
+    fn instantiate(&mut self, scheme: &Scheme) -> Ty {
+        let mapping = scheme
+            .variables
+            .iter()
+            .map(|var| (*var, self.fresh_var()))
+            .collect::<HashMap<_, _>>();
+        for (var, fields) in &scheme.lacks {
+            self.lacks
+                .entry(mapping[var])
+                .or_default()
+                .extend(fields.iter().cloned());
+        }
+        for (var, classes) in &scheme.classes {
+            self.classes
+                .entry(mapping[var])
+                .or_default()
+                .extend(classes.iter().copied());
+        }
+
+        let mapping = mapping
+            .into_iter()
+            .map(|(var, fresh)| (var, Ty::Var(fresh)))
+            .collect();
+        self.resolve(&scheme.ty.substitute(&mapping))
+    }
+
+    /// Makes generic the variables of `ty` that the enclosing bindings don't
+    /// constrain. Numbers whose type is still unknown become integers, so
+    /// that `inc = x => x + 1` is an `Int -> Int`.
+    fn generalize(&mut self, ty: &Ty) -> Scheme {
+        let constrained = self
+            .locals
+            .iter()
+            .flat_map(|(_, scheme)| {
+                self.resolve(&scheme.ty)
+                    .variables()
+                    .into_iter()
+                    .filter(|var| !scheme.variables.contains(var))
+            })
+            .collect::<HashSet<_>>();
+        for var in self.resolve(ty).variables() {
+            if !constrained.contains(&var) {
+                self.default_numbers(&Ty::Var(var));
+            }
+        }
+
+        let ty = self.resolve(ty);
+        let variables = ty
+            .variables()
+            .into_iter()
+            .filter(|var| !constrained.contains(var))
+            .collect::<Vec<_>>();
+        let lacks = variables
+            .iter()
+            .filter_map(|var| Some((*var, self.lacks.get(var)?.clone())))
+            .collect();
+        let classes = variables
+            .iter()
+            .filter_map(|var| Some((*var, self.classes.get(var)?.clone())))
+            .collect();
+
+        Scheme {
+            variables,
+            lacks,
+            classes,
+            ty,
+        }
+    }
+
+    /// The type of an annotation, with fresh variables for its type
+    /// variables. Those of `rigid` annotations stand for any type, so that
+    /// `id : a -> a = ...` is checked to be really generic.
+    fn annotation(&mut self, annotation: &ast::TypeExpr, rigid: bool) -> TypeResult<Ty> {
+        let variables = type_variables(annotation)
+            .into_iter()
+            .map(|name| {
+                let var = self.fresh_var();
+                if rigid {
+                    self.rigid.insert(var);
+                }
+                (name, Ty::Var(var))
+            })
+            .collect::<HashMap<_, _>>();
+        let (classes, annotation) = constraints(annotation, &variables)?;
+        for (var, classes) in classes {
+            self.classes.entry(var).or_default().extend(classes);
+        }
+        self.env.convert(annotation, &variables)
+    }
+
+    fn lookup(&self, name: &str) -> Option<&Scheme> {
+        self.locals
+            .iter()
+            .rev()
+            .find(|(local, _)| local == name)
+            .map(|(_, scheme)| scheme)
+            .or_else(|| self.env.values.get(name))
+    }
+
+    /// The scheme of an annotated binding, generic over the variables of
+    /// its annotation.
+    fn declared(&self, ty: &Ty) -> Scheme {
+        let ty = self.resolve(ty);
+        let variables = ty.variables();
+        let lacks = variables
+            .iter()
+            .filter_map(|var| Some((*var, self.lacks.get(var)?.clone())))
+            .collect();
+        let classes = variables
+            .iter()
+            .filter_map(|var| Some((*var, self.classes.get(var)?.clone())))
+            .collect();
+
+        Scheme {
+            variables,
+            lacks,
+            classes,
+            ty,
+        }
+    }
+
+    fn infer(&mut self, expr: &ast::Expr) -> TypeResult<Ty> {
+        match &expr.kind {
+            ExprKind::Literal(literal) => Ok(self.literal(literal)),
+            ExprKind::Identifier(ident) => match self.lookup(&ident.name).cloned() {
+                Some(scheme) => Ok(self.instantiate(&scheme)),
+                None if ident.name == GET => Err(TypeError::FieldName { span: ident.span }),
+                None if is_constructor(&ident.name) => Err(TypeError::UnknownConstructor {
+                    name: ident.name.clone(),
+                    span: ident.span,
+                }),
+                None => Err(TypeError::UnboundVariable {
+                    name: ident.name.clone(),
+                    span: ident.span,
+                }),
+            },
+            ExprKind::FunctionCall(call) => {
+                if let Some((record, field)) = self.get_field(call) {
+                    let ty = self.infer(record)?;
+                    let (fields, _) = self.expect_fields(&ty, &[&field], record.span)?;
+                    return Ok(fields[&field.name].clone());
+                }
+
+                let function = self.infer(&call.function)?;
+                let (from, to) = match self.shallow_non_number(&function) {
+                    Ty::Function(from, to) => (*from, *to),
+                    Ty::Var(_) => {
+                        let (from, to) = (self.fresh(), self.fresh());
+                        self.expect(
+                            &function,
+                            &Ty::function(from.clone(), to.clone()),
+                            expr.span,
+                        )?;
+                        (from, to)
+                    }
+                    other => {
+                        return Err(TypeError::NotAFunction {
+                            found: self.resolve(&other).to_string(),
+                            span: call.function.span,
+                        })
+                    }
+                };
+                self.check(&call.argument, &from)?;
+                Ok(to)
+            }
+            ExprKind::Product(fields) => Ok(Ty::Product {
+                fields: self.infer_fields(fields)?,
+                rest: None,
+            }),
+            ExprKind::Tuple(elements) => Ok(Ty::Tuple(
+                elements
+                    .iter()
+                    .map(|element| self.infer(element))
+                    .collect::<TypeResult<_>>()?,
+            )),
+            ExprKind::FieldAccess(access) => {
+                let record = self.infer(&access.record)?;
+                let (fields, _) =
+                    self.expect_fields(&record, &[&access.field], access.record.span)?;
+                Ok(fields[&access.field.name].clone())
+            }
+            ExprKind::Extension(extension) => {
+                let record = self.infer(&extension.record)?;
+                let span = extension.record.span;
+                match self.shallow_non_number(&record) {
+                    Ty::Product { fields, .. } => {
+                        if let Some((name, _)) = extension
+                            .fields
+                            .iter()
+                            .find(|(name, _)| fields.contains_key(&name.name))
+                        {
+                            return Err(TypeError::ExistingField {
+                                field: name.name.clone(),
+                                span: name.span,
+                            });
+                        }
+                    }
+                    Ty::Var(_) => {}
+                    other => {
+                        return Err(TypeError::NotARecord {
+                            found: self.resolve(&other).to_string(),
+                            span,
+                        })
+                    }
+                }
+
+                let rest = self.fresh_var();
+                self.lacks.insert(
+                    rest,
+                    extension
+                        .fields
+                        .iter()
+                        .map(|(n, _)| n.name.clone())
+                        .collect(),
+                );
+                let empty = Ty::Product {
+                    fields: BTreeMap::new(),
+                    rest: Some(rest),
+                };
+                self.expect(&empty, &record, span)?;
+
+                Ok(Ty::Product {
+                    fields: self.infer_fields(&extension.fields)?,
+                    rest: Some(rest),
+                })
+            }
+            ExprKind::Update(update) => {
+                let record = self.infer(&update.record)?;
+                let names = update
+                    .fields
+                    .iter()
+                    .map(|(name, _)| name)
+                    .collect::<Vec<_>>();
+                let (_, rest) = self.expect_fields(&record, &names, update.record.span)?;
+
+                // The new values may have other types than the old ones
+                Ok(Ty::Product {
+                    fields: self.infer_fields(&update.fields)?,
+                    rest: Some(rest),
+                })
+            }
+            ExprKind::Lambda(lambda) => {
+                let from = self.fresh();
+                let depth = self.locals.len();
+                self.bind_pattern(&lambda.from, &from)?;
+                let to = self.infer(&lambda.to);
+                self.locals.truncate(depth);
+                Ok(Ty::function(from, to?))
+            }
+            ExprKind::If(_) | ExprKind::Let(_) | ExprKind::Match(_) => {
+                let ty = self.fresh();
+                self.check(expr, &ty)?;
+                Ok(ty)
+            }
+            ExprKind::Ascription(ascription) => {
+                let ty = self.annotation(&ascription.ty, false)?;
+                self.check(&ascription.expr, &ty)?;
+                Ok(ty)
+            }
+            ExprKind::Interpolation(parts) => {
+                for part in parts {
+                    if let ast::StringPart::Expr(expr) = part {
+                        let ty = self.infer(expr)?;
+                        let shown = self.fresh_var();
+                        self.classes.insert(shown, BTreeSet::from([Class::Show]));
+                        self.expect(&Ty::Var(shown), &ty, expr.span)?;
+                    }
+                }
+                Ok(Ty::Primitive(Primitive::String))
+            }
+            ExprKind::Todo => Ok(self.fresh()),
+        }
+    }
+
+    /// Checks an expression against the type that its context expects. The
+    /// type flows into lambdas and branches, so that errors point at the
+    /// innermost expression that doesn't fit, rather than at the whole.
+    fn check(&mut self, expr: &ast::Expr, expected: &Ty) -> TypeResult<()> {
+        match (&expr.kind, self.shallow(expected)) {
+            (ExprKind::Lambda(lambda), Ty::Function(from, to)) => {
+                let depth = self.locals.len();
+                self.bind_pattern(&lambda.from, &from)?;
+                let result = self.check(&lambda.to, &to);
+                self.locals.truncate(depth);
+                result
+            }
+            (ExprKind::If(conditional), _) => {
+                self.check(&conditional.condition, &Ty::Primitive(Primitive::Bool))?;
+                self.check(&conditional.then_branch, expected)?;
+                self.check(&conditional.else_branch, expected)
+            }
+            (ExprKind::Let(binding), _) => {
+                let depth = self.locals.len();
+                self.infer_bindings(&binding.bindings)?;
+                let result = self.check(&binding.body, expected);
+                self.locals.truncate(depth);
+                result
+            }
+            (ExprKind::Match(matching), _) => {
+                let scrutinee = self.infer(&matching.scrutinee)?;
+                for arm in &matching.arms {
+                    let depth = self.locals.len();
+                    self.bind_pattern(&arm.pattern, &scrutinee)?;
+                    let result = self.check(&arm.body, expected);
+                    self.locals.truncate(depth);
+                    result?;
+                }
+                self.warnings.extend(check_match(self.env, matching));
+                Ok(())
+            }
+            _ => {
+                let found = self.infer(expr)?;
+                self.expect(expected, &found, expr.span)
+            }
+        }
+    }
+
+    fn infer_fields(
+        &mut self,
+        fields: &[(ast::Identifier, ast::Expr)],
+    ) -> TypeResult<BTreeMap<String, Ty>> {
+        fields
+            .iter()
+            .map(|(name, value)| Ok((name.name.clone(), self.infer(value)?)))
+            .collect()
+    }
+
+    /// The record and the field of `get record "field"`, which is typed like
+    /// `record.field`, unless `get` has been bound by the program.
+    fn get_field<'e>(
+        &self,
+        call: &'e ast::FunctionCall,
+    ) -> Option<(&'e ast::Expr, ast::Identifier)> {
+        let ExprKind::FunctionCall(inner) = &call.function.kind else {
+            return None;
+        };
+        let ExprKind::Identifier(function) = &inner.function.kind else {
+            return None;
+        };
+        let ExprKind::Literal(ast::Literal::String(name)) = &call.argument.kind else {
+            return None;
+        };
+        if function.name != GET || self.lookup(GET).is_some() {
+            return None;
+        }
+
+        let field = ast::Identifier {
+            name: name.clone(),
+            span: call.argument.span,
+        };
+        Some((&inner.argument, field))
+    }
+
+    /// Checks that `record` is a record with at least the fields `names`, and
+    /// returns their types together with the row of the other fields.
+    fn expect_fields(
+        &mut self,
+        record: &Ty,
+        names: &[&ast::Identifier],
+        span: Span,
+    ) -> TypeResult<(BTreeMap<String, Ty>, TyVar)> {
+        match self.shallow_non_number(record) {
+            Ty::Product { fields, rest: None } => {
+                if let Some(name) = names.iter().find(|name| !fields.contains_key(&name.name)) {
+                    self.default_numbers(record);
+                    return Err(TypeError::MissingField {
+                        record: self.resolve(record).to_string(),
+                        field: name.name.clone(),
+                        available: fields.keys().cloned().collect(),
+                        span: name.span,
+                    });
+                }
+            }
+            Ty::Product { .. } | Ty::Var(_) => {}
+            other => {
+                return Err(TypeError::NotARecord {
+                    found: self.resolve(&other).to_string(),
+                    span,
+                })
+            }
+        }
+
+        let fields = names
+            .iter()
+            .map(|name| (name.name.clone(), self.fresh()))
+            .collect::<BTreeMap<_, _>>();
+        let rest = self.fresh_var();
+        let ty = Ty::Product {
+            fields: fields.clone(),
+            rest: Some(rest),
+        };
+        self.expect(&ty, record, span)?;
+        Ok((fields, rest))
+    }
+
+    /// Checks that a pattern can match values of type `expected`, and brings
+    /// its variables into scope.
+    fn bind_pattern(&mut self, pattern: &ast::Pattern, expected: &Ty) -> TypeResult<()> {
+        match &pattern.kind {
+            PatternKind::Wildcard => Ok(()),
+            PatternKind::Variable(name) => {
+                self.locals
+                    .push((name.name.clone(), Scheme::mono(expected.clone())));
+                Ok(())
+            }
+            PatternKind::Literal(literal) => {
+                let ty = self.literal(literal);
+                self.expect(expected, &ty, pattern.span)
+            }
+            PatternKind::Constructor(name, payload) => {
+                let constructor =
+                    self.env
+                        .constructors
+                        .get(&name.name)
+                        .cloned()
+                        .ok_or_else(|| TypeError::UnknownConstructor {
+                            name: name.name.clone(),
+                            span: name.span,
+                        })?;
+
+                let mapping = constructor
+                    .parameters
+                    .iter()
+                    .map(|var| (*var, self.fresh()))
+                    .collect::<HashMap<_, _>>();
+                let ty = Ty::Sum {
+                    name: constructor.type_name.clone(),
+                    arguments: constructor
+                        .parameters
+                        .iter()
+                        .map(|var| mapping[var].clone())
+                        .collect(),
+                };
+                self.expect(expected, &ty, pattern.span)?;
+
+                match (payload, &constructor.payload) {
+                    (Some(payload), Some(ty)) => {
+                        self.bind_pattern(payload, &ty.substitute(&mapping))
+                    }
+                    (None, None) => Ok(()),
+                    (payload, _) => Err(TypeError::ConstructorPayload {
+                        name: name.name.clone(),
+                        expected: payload.is_none(),
+                        span: pattern.span,
+                    }),
+                }
+            }
+            PatternKind::Record(fields) => {
+                let names = fields.iter().map(|(name, _)| name).collect::<Vec<_>>();
+                let (ty, _) = self.expect_fields(expected, &names, pattern.span)?;
+
+                fields
+                    .iter()
+                    .try_for_each(|(name, field)| self.bind_pattern(field, &ty[&name.name]))
+            }
+            PatternKind::Annotated(inner, annotation) => {
+                let ty = self.annotation(annotation, false)?;
+                self.expect(&ty, expected, pattern.span)?;
+                self.bind_pattern(inner, &ty)
+            }
+            PatternKind::Tuple(elements) => {
+                let types = elements.iter().map(|_| self.fresh()).collect::<Vec<_>>();
+                self.expect(expected, &Ty::Tuple(types.clone()), pattern.span)?;
+                elements
+                    .iter()
+                    .zip(&types)
+                    .try_for_each(|(element, ty)| self.bind_pattern(element, ty))
+            }
+        }
+    }
+
+    /// Infers a group of bindings that can refer to each other, and brings
+    /// them into scope. Bindings are generalized one strongly connected
+    /// component at a time, so that using a binding doesn't fix the type of
+    /// the ones it doesn't depend on.
+    ///
+    /// Annotated bindings have the type of their annotation from the start,
+    /// so the others can already use them generically.
+    fn infer_bindings(&mut self, bindings: &[ast::Binding]) -> TypeResult<()> {
+        for component in components(bindings) {
+            let depth = self.locals.len();
+            let mut types = Vec::new();
+            for &i in &component {
+                let (ty, scheme) = match &bindings[i].annotation {
+                    Some(annotation) => {
+                        let ty = self.annotation(annotation, true)?;
+                        let scheme = self.declared(&ty);
+                        (ty, scheme)
+                    }
+                    None => {
+                        let ty = self.fresh();
+                        (ty.clone(), Scheme::mono(ty))
+                    }
+                };
+                self.locals.push((bindings[i].lhs.name.clone(), scheme));
+                types.push(ty);
+            }
+
+            for (&i, ty) in component.iter().zip(&types) {
+                self.check(&bindings[i].rhs, ty)?;
+            }
+
+            self.locals.truncate(depth);
+            let schemes = component
+                .iter()
+                .zip(&types)
+                .map(|(&i, ty)| match bindings[i].annotation {
+                    Some(_) => self.declared(ty),
+                    None => self.generalize(ty),
+                })
+                .collect::<Vec<_>>();
+            for (&i, scheme) in component.iter().zip(schemes) {
+                self.locals.push((bindings[i].lhs.name.clone(), scheme));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The outermost constructor of a type, `None` for a variable.
+fn head(ty: &Ty) -> Option<Head<'_>> {
+    match ty {
+        Ty::Var(_) => None,
+        Ty::Primitive(primitive) => Some(Head::Primitive(*primitive)),
+        Ty::Function(..) => Some(Head::Function),
+        Ty::Tuple(_) => Some(Head::Tuple),
+        Ty::Product { .. } => Some(Head::Record),
+        Ty::Sum { name, .. } => Some(Head::Sum(name)),
+    }
+}
+
+/// Splits a group of bindings into strongly connected components, each
+/// after the components it depends on, with Tarjan's algorithm.
+fn components(bindings: &[ast::Binding]) -> Vec<Vec<usize>> {
+    let index = bindings
+        .iter()
+        .enumerate()
+        .map(|(i, binding)| (binding.lhs.name.as_str(), i))
+        .collect::<HashMap<_, _>>();
+    let edges = bindings
+        .iter()
+        .map(|binding| {
+            let mut dependencies = free_variables(&binding.rhs)
+                .iter()
+                .filter_map(|name| index.get(name.as_str()).copied())
+                .collect::<Vec<_>>();
+            dependencies.sort();
+            dependencies
+        })
+        .collect::<Vec<_>>();
+
+    struct Tarjan {
+        edges: Vec<Vec<usize>>,
+        index: Vec<Option<usize>>,
+        low: Vec<usize>,
+        stack: Vec<usize>,
+        on_stack: Vec<bool>,
+        components: Vec<Vec<usize>>,
+    }
+
+    impl Tarjan {
+        fn visit(&mut self, v: usize, next: &mut usize) {
+            self.index[v] = Some(*next);
+            self.low[v] = *next;
+            *next += 1;
+            self.stack.push(v);
+            self.on_stack[v] = true;
+
+            for w in self.edges[v].clone() {
+                match self.index[w] {
+                    None => {
+                        self.visit(w, next);
+                        self.low[v] = self.low[v].min(self.low[w]);
+                    }
+                    Some(i) if self.on_stack[w] => self.low[v] = self.low[v].min(i),
+                    Some(_) => {}
+                }
+            }
+
+            if Some(self.low[v]) == self.index[v] {
+                let mut component = Vec::new();
+                while let Some(w) = self.stack.pop() {
+                    self.on_stack[w] = false;
+                    component.push(w);
+                    if w == v {
+                        break;
+                    }
+                }
+                component.sort();
+                self.components.push(component);
+            }
+        }
+    }
+
+    let n = bindings.len();
+    let mut tarjan = Tarjan {
+        edges,
+        index: vec![None; n],
+        low: vec![0; n],
+        stack: Vec::new(),
+        on_stack: vec![false; n],
+        components: Vec::new(),
+    };
+    let mut next = 0;
+    for v in 0..n {
+        if tarjan.index[v].is_none() {
+            tarjan.visit(v, &mut next);
+        }
+    }
+    tarjan.components
+}
+
+/// The names an expression refers to without binding them itself.
+fn free_variables(expr: &ast::Expr) -> HashSet<String> {
+    fn visit(expr: &ast::Expr, bound: &mut Vec<String>, free: &mut HashSet<String>) {
+        match &expr.kind {
+            ExprKind::Literal(_) | ExprKind::Todo => {}
+            ExprKind::Interpolation(parts) => {
+                for part in parts {
+                    if let ast::StringPart::Expr(expr) = part {
+                        visit(expr, bound, free);
+                    }
+                }
+            }
+            ExprKind::Identifier(ident) => {
+                if !bound.contains(&ident.name) {
+                    free.insert(ident.name.clone());
+                }
+            }
+            ExprKind::FunctionCall(call) => {
+                visit(&call.function, bound, free);
+                visit(&call.argument, bound, free);
+            }
+            ExprKind::Product(fields) => fields.iter().for_each(|(_, e)| visit(e, bound, free)),
+            ExprKind::Tuple(elements) => elements.iter().for_each(|e| visit(e, bound, free)),
+            ExprKind::FieldAccess(access) => visit(&access.record, bound, free),
+            ExprKind::Ascription(ascription) => visit(&ascription.expr, bound, free),
+            ExprKind::Extension(ast::Extension { fields, record })
+            | ExprKind::Update(ast::Update { record, fields }) => {
+                fields.iter().for_each(|(_, e)| visit(e, bound, free));
+                visit(record, bound, free);
+            }
+            ExprKind::Lambda(lambda) => {
+                let depth = bound.len();
+                bound.extend(lambda.from.variables().into_iter().map(|v| v.name.clone()));
+                visit(&lambda.to, bound, free);
+                bound.truncate(depth);
+            }
+            ExprKind::If(conditional) => {
+                visit(&conditional.condition, bound, free);
+                visit(&conditional.then_branch, bound, free);
+                visit(&conditional.else_branch, bound, free);
+            }
+            ExprKind::Let(binding) => {
+                let depth = bound.len();
+                bound.extend(binding.bindings.iter().map(|b| b.lhs.name.clone()));
+                binding
+                    .bindings
+                    .iter()
+                    .for_each(|b| visit(&b.rhs, bound, free));
+                visit(&binding.body, bound, free);
+                bound.truncate(depth);
+            }
+            ExprKind::Match(matching) => {
+                visit(&matching.scrutinee, bound, free);
+                for arm in &matching.arms {
+                    let depth = bound.len();
+                    bound.extend(arm.pattern.variables().into_iter().map(|v| v.name.clone()));
+                    visit(&arm.body, bound, free);
+                    bound.truncate(depth);
+                }
+            }
+        }
+    }
+
+    let mut free = HashSet::new();
+    visit(expr, &mut Vec::new(), &mut free);
+    free
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::complete::parse;
+    use crate::eval::Scope;
+    use crate::source::Sources;
+    use rstest::rstest;
+    use std::ops::Range;
+
+    fn check(input: &str) -> TypeResult<Checked> {
+        let mut sources = Sources::new();
+        let file = sources.add("test.len", input);
+        let program = parse(&sources, file).unwrap();
+
+        check_program(&program, &Env::std())
+    }
+
+    /// The types of the bindings and of the body, one per line.
+    fn types(input: &str) -> String {
+        let checked = check(input).unwrap();
+        checked
+            .bindings
+            .iter()
+            .map(|(name, scheme)| format!("{name} : {scheme}"))
+            .chain(checked.body.map(|ty| ty.to_string()))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[rstest]
+    #[case("1 + 2", "Int")]
+    #[case("\"a\"", "String")]
+    #[case("id = x => x", "id : a -> a")]
+    #[case("id = x => x; (id 1, id \"a\")", "id : a -> a\n(Int, String)")]
+    #[case("f = x => x + 1", "f : Int -> Int")]
+    #[case(
+        "compose = f g x => f (g x)",
+        "compose : (a -> b) -> (c -> a) -> c -> b"
+    )]
+    #[case("const = a _ => a", "const : a -> b -> a")]
+    #[case("let id = x => x in if id true then id 1 else 2", "Int")]
+    #[case("x => let y = x in y", "a -> a")]
+    #[case("{ a: 1, b: \"c\" }.b", "String")]
+    #[case("(a, b) => { a: a, b: b }", "(a, b) -> { a: a, b: b }")]
+    #[case("{ a, b } => a + b", "{ a: a, b: a | b } -> a")]
+    #[case("r => r.name", "{ name: a | b } -> a")]
+    #[case(
+        "name = r => r.name; (name { name: 1 }, name { name: \"a\", age: 2 })",
+        "name : { name: a | b } -> a\n(Int, String)"
+    )]
+    #[case("r => (r.a + 1, r.b)", "{ a: Int, b: a | b } -> (Int, a)")]
+    #[case("r => { c: 1 | r }", "{ | a } -> { c: Int | a }")]
+    #[case("{ b: true | { a: 1 } }", "{ a: Int, b: Bool }")]
+    #[case("r => { r | a: \"a\" }", "{ a: a | b } -> { a: String | b }")]
+    #[case("{ { a: 1, b: 2 } | a: \"a\" }", "{ a: String, b: Int }")]
+    #[case(
+        "f = r => { c: 1 | r }; f { a: 1 }",
+        "f : { | a } -> { c: Int | a }\n{ a: Int, c: Int }"
+    )]
+    #[case(
+        "fact = n => if n == 0 then 1 else n * fact (n - 1)",
+        "fact : Int -> Int"
+    )]
+    #[case(
+        "even = n => if n == 0 then true else odd (n - 1)\nodd = n => if n == 0 then false else even (n - 1)",
+        "even : Int -> Bool\nodd : Int -> Bool"
+    )]
+    #[case("id = x => x\nuse = id 1\nid", "id : a -> a\nuse : Int\na -> a")]
+    #[case("type Option a = Some a | None\nSome", "a -> Option a")]
+    #[case(
+        "type Option a = Some a | None\nget_or = d o => match o { Some x => x, None => d }",
+        "get_or : a -> Option a -> a"
+    )]
+    #[case(
+        "length = l => match l { Nil => 0, Cons (_, tail) => 1 + length tail }",
+        "length : List a -> Int"
+    )]
+    #[case("x : Int = 3", "x : Int")]
+    #[case("id : a -> a = x => x; id", "id : a -> a\na -> a")]
+    #[case("(n : Int) => n", "Int -> Int")]
+    #[case("f = (x : a) => x + 1", "f : Int -> Int")]
+    #[case("(1, \"a\") : (Int, String)", "(Int, String)")]
+    #[case(
+        "name : { name: String | r } -> String = r => r.name\nname { name: \"a\", age: 1 }",
+        "name : { name: String | a } -> String\nString"
+    )]
+    #[case(
+        "type Option a = Some a | None\nnone : Option Int = None\nlet x : Option a = None in x",
+        "none : Option Int\nOption a"
+    )]
+    #[case("\"a\" + \"b\"", "String")]
+    #[case("Cons (1, Nil) + Nil", "List Int")]
+    #[case("add = a b => a + b", "add : Add a => a -> a -> a")]
+    #[case("eq = a b => a == b", "eq : Eq a => a -> a -> Bool")]
+    #[case("(1, \"a\") == (2, \"b\")", "Bool")]
+    #[case(
+        "lt = a b => a < b; (lt 1 2, lt \"a\" \"b\")",
+        "lt : Ord a => a -> a -> Bool\n(Bool, Bool)"
+    )]
+    #[case("r => r.a == 1", "{ a: Int | a } -> Bool")]
+    #[case(
+        "f = a b => if a == b then a + b else b; f",
+        "f : (Add a, Eq a) => a -> a -> a\na -> a -> a"
+    )]
+    #[case(
+        "f : (Add a, Eq a) => a -> a -> Bool = x y => x + y == y",
+        "f : (Add a, Eq a) => a -> a -> Bool"
+    )]
+    #[case("1.5", "Rational")]
+    #[case("1 + 2.5 * 2", "Rational")]
+    #[case("0xff - 0b1", "Int")]
+    #[case("half = x => x * 0.5", "half : Rational -> Rational")]
+    #[case("x : Rational = 1; x < 1.5", "x : Rational\nBool")]
+    #[case("\"a ${1} b\"", "String")]
+    #[case("greet = name => \"hello ${name}!\"", "greet : Show a => a -> String")]
+    #[case("show = x => \"${(x, 1)}\"", "show : Show a => a -> String")]
+    #[case(
+        "twice : Num a => a -> a = x => x + x; (twice 1, twice 1.5)",
+        "twice : Num a => a -> a\n(Int, Rational)"
+    )]
+    #[case("{ a: 1 }", "{ a: Int }")]
+    #[case("f = x => x - 1", "f : Int -> Int")]
+    #[case("x = 1; rational x + 0.5", "x : Int\nRational")]
+    #[case(
+        "mean : Num a => a -> a -> Rational = x y => (x + y) / 2",
+        "mean : Num a => a -> a -> Rational"
+    )]
+    #[case("inc 1 / 2", "Rational")]
+    fn test_infer(#[case] input: &str, #[case] expected: &str) {
+        assert_eq!(types(input), expected);
+    }
+
+    #[rstest]
+    #[case("1 + \"a\"", "mismatched types", 4..7)]
+    #[case("if 1 then 2 else 3", "mismatched types", 3..4)]
+    #[case("if true then 2 else \"a\"", "mismatched types", 20..23)]
+    #[case("f = 3; f 1", "expected a function, found `Int`", 7..8)]
+    #[case("foo + 1", "unbound variable `foo`", 0..3)]
+    #[case("get { a: 1 } \"a\" \"x\"", "expected a function, found `Int`", 0..16)]
+    #[case("rational \"1\"", "mismatched types", 9..12)]
+    #[case("get { a: 1 } \"b\"", "type `{ a: Int }` has no field `b`", 13..16)]
+    #[case("x = \"a\"; get { a: 1 } x", "`get` needs a record and the name of its field as a string literal", 9..12)]
+    #[case("f = get; f { a: 1 } \"a\"", "`get` needs a record and the name of its field as a string literal", 4..7)]
+    #[case("get 1 \"a\"", "expected a record, found `Int`", 4..5)]
+    #[case("{ a: 1 }.a \"x\"", "expected a function, found `Int`", 0..10)]
+    #[case("x => x x", "cannot construct the infinite type `a = a -> b`", 7..8)]
+    #[case("{ a: 1 }.b", "type `{ a: Int }` has no field `b`", 9..10)]
+    #[case("{ a: 1 | { a: 2 } }", "the record already has a field `a`", 2..3)]
+    #[case("f = r => { a: 1 | r }; f { a: 2 }", "the record already has a field `a`", 25..33)]
+    #[case("f = r => r.a; f { b: 2 }", "mismatched types", 16..24)]
+    #[case("{ { a: 1 } | b: 2 }", "type `{ a: Int }` has no field `b`", 13..14)]
+    #[case("({ a } => a) 1", "mismatched types", 13..14)]
+    #[case("1.a", "expected a record, found `Int`", 0..1)]
+    #[case("f = x => x + 1; f true", "mismatched types", 18..22)]
+    #[case("id = x => x; f = g => (g 1, g true); f id", "mismatched types", 30..34)]
+    #[case("type T = A Missing", "unknown type `Missing`", 11..18)]
+    #[case("type T a = A (T a a)", "type `T` expects 1 arguments, found 2", 13..20)]
+    #[case("type T = A b", "unbound type variable `b`", 11..12)]
+    #[case("match 1 { Sme x => x }", "unknown constructor `Sme`", 10..13)]
+    #[case("type T = A Int\nmatch A 1 { A => 1 }", "constructor `A` takes a value", 27..28)]
+    #[case("type T = A | B\nmatch A { A => 1, B => \"b\" }", "mismatched types", 38..41)]
+    #[case("x : String = 3", "mismatched types", 13..14)]
+    #[case("f : a -> a = x => x + 1", "type `a` is not an instance of `Add`", 18..19)]
+    #[case("f : Add a => a -> a = x => x + 1", "type `a` is not an instance of `Num`", 31..32)]
+    #[case("f : a -> b = x => x", "mismatched types", 18..19)]
+    #[case("x : Int = if true then \"a\" else 1", "mismatched types", 23..26)]
+    #[case("f : Int -> String = x => let y = x in y", "mismatched types", 38..39)]
+    #[case("1 + 1 : String", "mismatched types", 0..5)]
+    #[case("((n : String) => n) 1", "mismatched types", 20..21)]
+    #[case("x : Maybe Int = 1", "unknown type `Maybe`", 4..9)]
+    #[case("f : { a: Int | r } -> Int = r => r.b", "mismatched types", 33..34)]
+    #[case("true + true", "type `Bool` is not an instance of `Add`", 0..4)]
+    #[case("(x => x) == (x => x)", "type `a -> a` is not an instance of `Eq`", 0..8)]
+    #[case(
+        "type F = F (Int -> Int)\nF inc == F inc",
+        "type `Int -> Int` is not an instance of `Eq`",
+        24..29
+    )]
+    #[case("(1, inc) == (1, inc)", "type `Int -> Int` is not an instance of `Eq`", 0..8)]
+    #[case(
+        "f = (r : { a: Int | s }) => r == r; f { a: 1, b: inc }",
+        "type `Int -> Int` is not an instance of `Eq`",
+        38..54
+    )]
+    #[case("true < false", "type `Bool` is not an instance of `Ord`", 0..4)]
+    #[case("\"${x => x}\"", "type `a -> a` is not an instance of `Show`", 3..9)]
+    #[case("\"${{ f: x => x }}\"", "type `a -> a` is not an instance of `Show`", 3..16)]
+    #[case("f : a -> a -> Bool = x y => x == y", "type `a` is not an instance of `Eq`", 28..29)]
+    #[case("f : Monad a => a -> a = x => x", "unknown class `Monad`", 4..9)]
+    #[case("x : Int = 1.5", "mismatched types", 10..13)]
+    #[case("1.5 + \"a\"", "mismatched types", 6..9)]
+    #[case("1 2", "expected a function, found `Int`", 0..1)]
+    #[case("half = x => x * 0.5; half 1 + inc 1", "mismatched types", 30..35)]
+    #[case("f : Eq b => a -> a = x => x", "unbound type variable `b`", 7..8)]
+    #[case("type List a = Nil", "type `List` is built in, and can't be declared again", 5..9)]
+    fn test_errors(#[case] input: &str, #[case] message: &str, #[case] span: Range<usize>) {
+        let err = check(input).unwrap_err();
+        assert_eq!(err.to_string(), message);
+        assert_eq!(err.span().start..err.span().end, span);
+    }
+
+    #[test]
+    fn test_mismatch_types() {
+        match check("f = x => x + 1; f \"a\"") {
+            Err(TypeError::Mismatch {
+                expected, found, ..
+            }) => assert_eq!((expected.as_str(), found.as_str()), ("Int", "String")),
+            other => panic!("expected a mismatch, got {other:?}"),
+        }
+    }
+
+    /// Every built-in value has a type, and every type a value. `get` is
+    /// typed where it is called instead.
+    #[test]
+    fn test_std() {
+        let mut values = Scope::std()
+            .bindings
+            .keys()
+            .filter(|name| *name != GET)
+            .cloned()
+            .collect::<Vec<_>>();
+        values.sort();
+        let mut types = Env::std().values.keys().cloned().collect::<Vec<_>>();
+        types.sort();
+        assert_eq!(values, types);
+    }
+}
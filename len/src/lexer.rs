@@ -1,17 +1,31 @@
-use crate::Int;
+use crate::number::{self, format_rational, Number};
+use crate::source::FileId;
+use crate::{Int, Rational};
 use chumsky::extra::ParserExtra;
-use chumsky::input::SpannedInput;
-use chumsky::input::StrInput;
 use chumsky::input::WithContext;
+use chumsky::input::{InputRef, StrInput};
 use chumsky::prelude::*;
 use chumsky::text::Char;
+use chumsky::util::MaybeRef;
 use chumsky::Parser;
+use std::fmt;
+use std::ops::Range;
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub enum TokenKind<'src> {
     Number(Int),
+    /// A number literal with a fraction or an exponent, like `1.5e3`.
+    Decimal(Rational),
     Bind,
-    String(&'src str),
+    /// The value of a string literal, with its escapes decoded.
+    String(String),
+    /// The text of an interpolated string up to its first `${`. The tokens
+    /// of the expression follow.
+    StringHead(String),
+    /// The text between the `}` of an interpolation and the next `${`.
+    StringMiddle(String),
+    /// The text after the last interpolation, up to the closing quote.
+    StringTail(String),
     Ident(&'src str),
     Symbol(&'src str),
     RightParenthesis,
@@ -20,22 +34,68 @@ pub enum TokenKind<'src> {
     LeftCurly,
     RightCurly,
     Comma,
+    Dot,
+    Underscore,
     Arrow,
+    Semicolon,
+    If,
+    Then,
+    Else,
+    Let,
+    In,
+    Type,
+    Match,
+    /// `## text`, the text of a doc comment up to the end of its line, when
+    /// it documents a statement. The others are [`Trivia::DocComment`], see
+    /// [`layout`].
+    DocComment(&'src str),
+    /// A line break that starts a new, non-indented line outside of any
+    /// brackets. See [`layout`].
+    Newline,
 }
 
-#[derive(Debug)]
-pub struct Token<'src> {
-    pub kind: TokenKind<'src>,
-    pub span: SimpleSpan,
+/// Prints the token as it appears in the source.
+impl fmt::Display for TokenKind<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenKind::Number(n) => write!(f, "{n}"),
+            TokenKind::Decimal(r) => write!(f, "{}", format_rational(r)),
+            TokenKind::String(s) => write!(f, "{s:?}"),
+            TokenKind::StringHead(s) => write!(f, "{}${{", s.escape_debug()),
+            TokenKind::StringMiddle(s) => write!(f, "}}{}${{", s.escape_debug()),
+            TokenKind::StringTail(s) => write!(f, "}}{}\"", s.escape_debug()),
+            TokenKind::Ident(s) | TokenKind::Symbol(s) => write!(f, "{s}"),
+            TokenKind::Bind => write!(f, "="),
+            TokenKind::RightParenthesis => write!(f, ")"),
+            TokenKind::LeftParenthesis => write!(f, "("),
+            TokenKind::Colon => write!(f, ":"),
+            TokenKind::LeftCurly => write!(f, "{{"),
+            TokenKind::RightCurly => write!(f, "}}"),
+            TokenKind::Comma => write!(f, ","),
+            TokenKind::Dot => write!(f, "."),
+            TokenKind::Underscore => write!(f, "_"),
+            TokenKind::Arrow => write!(f, "=>"),
+            TokenKind::Semicolon => write!(f, ";"),
+            TokenKind::If => write!(f, "if"),
+            TokenKind::Then => write!(f, "then"),
+            TokenKind::Else => write!(f, "else"),
+            TokenKind::Let => write!(f, "let"),
+            TokenKind::In => write!(f, "in"),
+            TokenKind::Type => write!(f, "type"),
+            TokenKind::Match => write!(f, "match"),
+            TokenKind::DocComment(s) => write!(f, "##{s}"),
+            TokenKind::Newline => writeln!(f),
+        }
+    }
 }
 
 fn is_reserved_char(c: &char) -> bool {
-    r#"(),;[]`{}_:"'"#.chars().any(|reserved| reserved == c.to_char())
+    r#"(),;.[]`{}_:"'#"#.chars().any(|reserved| reserved == c.to_char())
 }
 
 #[must_use]
 fn symbol<'a, I: StrInput<'a, C>, C: Char, E: ParserExtra<'a, I>>(
-) -> impl Parser<'a, I, &'a C::Str, E> + Copy + Clone {
+) -> impl Parser<'a, I, &'a C::Str, E> + Copy {
     let f = |c: &C| {
         let c = c.to_char();
         !c.is_whitespace() && !c.is_alphanumeric() && !is_reserved_char(&c)
@@ -44,55 +104,316 @@ fn symbol<'a, I: StrInput<'a, C>, C: Char, E: ParserExtra<'a, I>>(
     any().filter(f).then(any().filter(f).repeated()).to_slice()
 }
 
-pub type LexerI<'a> = &'a str;
-pub type LexerO<'a> = Vec<Token<'a>>;
-
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub struct SpanContext {
-    source: SpanSource,
+    pub source: SpanSource,
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub enum SpanSource {
     #[default]
     Unknown,
-    File(String)
+    File(FileId),
+}
+
+impl SpanContext {
+    pub fn span(self, range: Range<usize>) -> Span {
+        chumsky::span::Span::new(self, range)
+    }
+}
+
+/// What the lexer reads between the tokens, which the parser never sees.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trivia {
+    /// Spaces and line breaks, except the [`TokenKind::Newline`]s that end
+    /// statements.
+    Whitespace,
+    /// Line and block comments.
+    Comment,
+    /// A doc comment inside a statement, like the ones of record fields and
+    /// match arms, which the parser skips.
+    DocComment,
+    /// Text that isn't the start of any token, which is reported as an error.
+    Error,
+}
+
+/// A token or the trivia around it, as read by [`lossless_lexer`].
+#[derive(Clone, PartialEq)]
+pub enum Lexeme<'src> {
+    Token(TokenKind<'src>),
+    Trivia(Trivia),
+}
+
+impl fmt::Debug for Lexeme<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Lexeme::Token(kind) => write!(f, "{kind:?}"),
+            Lexeme::Trivia(trivia) => write!(f, "{trivia:?}"),
+        }
+    }
 }
 
-type Span = chumsky::span::SimpleSpan<usize, SpanContext>;
-type Spanned<T> = (Span, T);
+/// Byte range in a source, together with the file it belongs to.
+pub type Span = chumsky::span::SimpleSpan<usize, SpanContext>;
+pub type Spanned<T> = (T, Span);
 
+pub type Token<'src> = Spanned<TokenKind<'src>>;
+
+pub type LexerI<'a> = WithContext<Span, &'a str>;
+pub type LexerO<'a> = Vec<Token<'a>>;
+
+/// The tokens of a source, for the parser.
 #[must_use]
-pub fn lexer_new<'s>() -> impl Parser<'s, WithContext<Span, &'s str>, Vec<Spanned<TokenKind<'s>>>> {
-    let number = text::int(10)
-        .map(str::parse)
-        .unwrapped()
-        .map(TokenKind::Number);
-
-    number
-        .padded()
-        .map_with(|elem, extra| (extra.span(), elem))
-        .repeated()
-        .collect()
+pub fn lexer<'s, E: ParserExtra<'s, LexerI<'s>>>(
+) -> impl Parser<'s, LexerI<'s>, LexerO<'s>, extra::Err<Rich<'s, char, Span>>> {
+    lossless_lexer().map(|lexemes| {
+        lexemes
+            .into_iter()
+            .filter_map(|(lexeme, span)| match lexeme {
+                Lexeme::Token(kind) => Some((kind, span)),
+                Lexeme::Trivia(_) => None,
+            })
+            .collect()
+    })
 }
 
+/// The tokens of a source together with the trivia between them, which
+/// cover the whole source. Text that isn't a token is kept as
+/// [`Trivia::Error`], so the lexer always reaches the end.
 #[must_use]
-pub fn lexer<'s, E: ParserExtra<'s, LexerI<'s>>>() -> impl Parser<'s, LexerI<'s>, LexerO<'s>, E> {
-    let number = text::int(10)
-        .map(str::parse)
-        .unwrapped()
-        .map(TokenKind::Number);
-
-    let string = any()
-        .filter(|c| *c != '"')
-        .repeated()
+pub fn lossless_lexer<'s>(
+) -> impl Parser<'s, LexerI<'s>, Vec<Spanned<Lexeme<'s>>>, extra::Err<Rich<'s, char, Span>>> {
+    // Malformed tokens are reported without failing, with a placeholder
+    // value, as the lexer only keeps one error for all the tokens that fail
+    // at the same place
+    let digit = any().filter(char::is_ascii_digit);
+
+    let alphanumeric = any()
+        .filter(|c: &char| c.is_alphanumeric() || *c == '_')
+        .ignored();
+
+    // Prefixed integers have no exponent, so `0x1e+1` is `0x1e + 1`
+    let prefixed = just('0')
+        .then(one_of("xob"))
+        .then(alphanumeric.repeated())
+        .ignored();
+
+    let decimal = digit
+        .then(
+            choice((
+                // The sign of an exponent
+                one_of("eE")
+                    .then(one_of("+-"))
+                    .then(digit.rewind())
+                    .ignored(),
+                // A dot only belongs to the number before a digit, `1.a` is
+                // a field access
+                just('.').then(digit.rewind()).ignored(),
+                alphanumeric,
+            ))
+            .repeated(),
+        )
+        .ignored();
+
+    // Takes everything that could belong to the literal, so that `0xZZ` or
+    // `12abc` are reported as malformed numbers instead of being split up
+    let number = choice((prefixed, decimal))
         .to_slice()
-        .map(TokenKind::String)
-        .delimited_by(just('"'), just('"'));
+        .validate(|text: &str, e, emitter| match number::parse(text) {
+            Ok(Number::Integer(n)) => TokenKind::Number(n),
+            Ok(Number::Decimal(r)) => TokenKind::Decimal(r),
+            Err(message) => {
+                emitter.emit(Rich::custom(e.span(), message));
+                TokenKind::Number(Int::default())
+            }
+        });
+
+    let unicode = just("u{")
+        .ignore_then(none_of("}\"").repeated().to_slice())
+        .then_ignore(just('}'))
+        .validate(|digits: &str, e, emitter| {
+            let c = u32::from_str_radix(digits, 16)
+                .ok()
+                .and_then(char::from_u32);
+            c.unwrap_or_else(|| {
+                let message = format!("`{digits}` is not the code of a unicode character");
+                emitter.emit(Rich::custom(e.span(), message));
+                char::REPLACEMENT_CHARACTER
+            })
+        });
+
+    let escape = just('\\').ignore_then(choice((
+        unicode,
+        any().validate(|c, e, emitter| match c {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            '"' => '"',
+            '\\' => '\\',
+            '$' => '$',
+            c => {
+                let message = match c {
+                    'u' => String::from("unicode escapes are written `\\u{1F600}`"),
+                    c => format!("unknown character escape `\\{c}`"),
+                };
+                emitter.emit(Rich::custom(e.span(), message));
+                c
+            }
+        }),
+    )));
+
+    // The tokens of the expression of an interpolation, which can only
+    // contain a `}` that closes one of its own `{`
+    let mut interpolated = Recursive::declare();
+
+    let dollar = just('$').and_is(just("${").not());
+
+    let text = choice((escape, none_of("\\\"$"), dollar))
+        .repeated()
+        .collect::<String>();
+
+    let interpolation = just("${")
+        .map_with(|_, e| -> Span { e.span() })
+        .then(interpolated.clone())
+        .then(just('}').map_with(|_, e| -> Span { e.span() }).or_not());
+
+    let line = just('"')
+        .map_with(|_, e| e.span())
+        .then(text)
+        .then(
+            interpolation
+                .clone()
+                .then(text)
+                .repeated()
+                .collect::<Vec<_>>(),
+        )
+        .then(just('"').or_not().map(|close| close.is_some()));
+
+    let block_text = choice((
+        escape.map(|c| (c, false)),
+        choice((
+            none_of("\\\"$"),
+            dollar,
+            just('"').and_is(just("\"\"\"").not()),
+        ))
+        .map(|c| (c, true)),
+    ))
+    .repeated()
+    .collect::<Vec<_>>();
+
+    // `"""` strings span several lines, and lose the indentation that their
+    // lines have in common
+    let block = just("\"\"\"")
+        .map_with(|_, e| e.span())
+        .then(block_text)
+        .then(
+            interpolation
+                .then(block_text)
+                .repeated()
+                .collect::<Vec<_>>(),
+        )
+        .then(just("\"\"\"").or_not().map(|close| close.is_some()))
+        .map(|(((open, head), interpolations), closed)| {
+            let (head, interpolations) = dedent(head, interpolations);
+            (((open, head), interpolations), closed)
+        });
+
+    // An unterminated string is reported at its opening quote, rather than
+    // at the end of the file where the lexer notices it
+    let string =
+        choice((block, line)).validate(|(((open, head), interpolations), closed), e, emitter| {
+            let span: Span = e.span();
+            if interpolations.is_empty() {
+                if !closed {
+                    emitter.emit(Rich::custom(open, "unterminated string"));
+                }
+                return vec![(Lexeme::Token(TokenKind::String(head)), span)];
+            }
+
+            // Each piece of text goes with the `}` before it and the `${`
+            // after it
+            let mut tokens = Vec::new();
+            let (mut text, mut start) = (head, span.start);
+            for (((dollar, expr), close), next) in interpolations {
+                let kind = match tokens.is_empty() {
+                    true => TokenKind::StringHead(text),
+                    false => TokenKind::StringMiddle(text),
+                };
+                tokens.push((Lexeme::Token(kind), span.context().span(start..dollar.end)));
+                tokens.extend(expr);
+                start = match close {
+                    Some(close) => close.start,
+                    None => {
+                        emitter.emit(Rich::custom(dollar, "unclosed interpolation"));
+                        tokens
+                            .last()
+                            .map_or(dollar.end, |(_, span): &Spanned<Lexeme>| span.end)
+                    }
+                };
+                text = next;
+            }
+            tokens.push((
+                Lexeme::Token(TokenKind::StringTail(text)),
+                span.context().span(start..span.end),
+            ));
+            if !closed {
+                emitter.emit(Rich::custom(open, "unterminated string"));
+            }
+            tokens
+        });
+
+    // `r#"..."#` takes everything, up to a quote followed by as many `#` as
+    // it started with
+    let raw_string = custom(|inp: &mut InputRef<'s, '_, LexerI<'s>, _>| {
+        let start = inp.offset();
+        let hashes = inp.parse(
+            just('r')
+                .ignore_then(just('#').repeated().count())
+                .then_ignore(just('"')),
+        )?;
+        let body = inp.offset();
+
+        loop {
+            let end = inp.offset();
+            match inp.next() {
+                Some('"') => {
+                    let mut closing = 0;
+                    while closing < hashes && inp.peek() == Some('#') {
+                        inp.skip();
+                        closing += 1;
+                    }
+                    if closing == hashes {
+                        return Ok((inp.slice(body..end).to_string(), None));
+                    }
+                }
+                Some(_) => {}
+                None => {
+                    let open = inp.span(start..body);
+                    return Ok((inp.slice_since(body..).to_string(), Some(open)));
+                }
+            }
+        }
+    })
+    .validate(|(s, unterminated), _, emitter| {
+        if let Some(open) = unterminated {
+            emitter.emit(Rich::custom(open, "unterminated raw string"));
+        }
+        TokenKind::String(s)
+    });
 
     let symbol = symbol().to_slice().map(TokenKind::Symbol);
 
-    let ident = chumsky::text::unicode::ident().map(TokenKind::Ident);
+    let ident = chumsky::text::unicode::ident().map(|s| match s {
+        "if" => TokenKind::If,
+        "then" => TokenKind::Then,
+        "else" => TokenKind::Else,
+        "let" => TokenKind::Let,
+        "in" => TokenKind::In,
+        "type" => TokenKind::Type,
+        "match" => TokenKind::Match,
+        s => TokenKind::Ident(s),
+    });
 
     let reserved = select! {
         ')' => TokenKind::RightParenthesis,
@@ -101,6 +422,9 @@ pub fn lexer<'s, E: ParserExtra<'s, LexerI<'s>>>() -> impl Parser<'s, LexerI<'s>
         '}' => TokenKind::RightCurly,
         '{' => TokenKind::LeftCurly,
         ',' => TokenKind::Comma,
+        '.' => TokenKind::Dot,
+        '_' => TokenKind::Underscore,
+        ';' => TokenKind::Semicolon,
     };
 
     let arrow = just('=').then(just('>')).to(TokenKind::Arrow);
@@ -116,26 +440,219 @@ pub fn lexer<'s, E: ParserExtra<'s, LexerI<'s>>>() -> impl Parser<'s, LexerI<'s>
         )
         .to(TokenKind::Bind);
 
-    choice((arrow, bind, number, reserved, symbol, string, ident))
-        .padded()
-        .map_with(|t: TokenKind, e| Token {
-            kind: t,
-            span: e.span(),
-        })
+    // A line break only ends a statement when the next line is not indented,
+    // every other line break is just whitespace.
+    let newline = just('\n')
+        .then(any().filter(|c: &char| !c.is_whitespace()).rewind())
+        .to(TokenKind::Newline);
+
+    let doc_comment = just("##")
+        .ignore_then(none_of('\n').repeated().to_slice())
+        .map(TokenKind::DocComment);
+
+    let line_comment = just('#')
+        .then_ignore(one_of("#[").not())
+        .then(none_of('\n').repeated())
+        .ignored();
+
+    // `#[ ... ]#`, which can contain other block comments
+    let block_comment = recursive(|block_comment| {
+        just("#[")
+            .map_with(|_, e| -> Span { e.span() })
+            .then_ignore(
+                choice((block_comment, any().and_is(just("]#").not()).ignored())).repeated(),
+            )
+            .then(just("]#").or_not())
+            .validate(|(open, close), _, emitter| {
+                if close.is_none() {
+                    emitter.emit(Rich::custom(open, "unterminated block comment"));
+                }
+            })
+    });
+
+    let trivia = choice((
+        any()
+            .filter(|c: &char| c.is_whitespace())
+            .and_is(newline.clone().not())
+            .repeated()
+            .at_least(1)
+            .to(Trivia::Whitespace),
+        line_comment.to(Trivia::Comment),
+        block_comment.to(Trivia::Comment),
+    ))
+    .map_with(|t, e| vec![(Lexeme::Trivia(t), e.span())]);
+
+    let token = choice((
+        arrow,
+        bind,
+        number,
+        reserved,
+        symbol,
+        raw_string,
+        ident,
+        doc_comment,
+        newline,
+    ))
+    .map_with(|t: TokenKind, e| vec![(Lexeme::Token(t), e.span())]);
+
+    let braced = just('{')
+        .map_with(|_, e| (Lexeme::Token(TokenKind::LeftCurly), e.span()))
+        .then(interpolated.clone())
+        .then(just('}').map_with(|_, e| (Lexeme::Token(TokenKind::RightCurly), e.span())))
+        .map(
+            |((open, mut tokens), close): ((Spanned<Lexeme>, Vec<_>), _)| {
+                tokens.insert(0, open);
+                tokens.push(close);
+                tokens
+            },
+        );
+
+    interpolated.define(
+        choice((
+            braced,
+            string.clone(),
+            one_of("{}").not().ignore_then(token.clone()),
+            trivia.clone(),
+        ))
         .repeated()
+        .collect::<Vec<_>>()
+        .map(|tokens| tokens.concat()),
+    );
+
+    let lexeme = choice((string, token, trivia));
+
+    // Reported once for each run of characters that no token starts with,
+    // the lexing goes on after it
+    let unexpected = any()
+        .and_is(lexeme.clone().not())
+        .repeated()
+        .at_least(1)
+        .to_slice()
+        .validate(|text: &str, e, emitter| {
+            let found = text.chars().next().map(MaybeRef::Val);
+            let error =
+                <Rich<_, _> as chumsky::error::Error<LexerI>>::expected_found([], found, e.span());
+            emitter.emit(error);
+            vec![(Lexeme::Trivia(Trivia::Error), e.span())]
+        });
+
+    choice((lexeme, unexpected))
+        .repeated()
+        .collect::<Vec<_>>()
+        .map(|lexemes| layout(lexemes.concat()))
+}
+
+/// Turns the [`TokenKind::Newline`]s that appear inside brackets into
+/// whitespace, so that only the line breaks between top-level statements
+/// are kept.
+///
+/// Doc comments that start a top-level line document the statement after
+/// them and separate it from the previous one. The others are kept as trivia,
+/// so that the parser skips them wherever they appear.
+fn layout(lexemes: Vec<Spanned<Lexeme>>) -> Vec<Spanned<Lexeme>> {
+    let mut depth = 0usize;
+    let mut line_start = true;
+
+    lexemes
+        .into_iter()
+        .map(|(lexeme, span)| {
+            let Lexeme::Token(kind) = &lexeme else {
+                return (lexeme, span);
+            };
+            let trivia = match kind {
+                TokenKind::DocComment(_) if depth > 0 || !line_start => Some(Trivia::DocComment),
+                TokenKind::Newline if depth > 0 => Some(Trivia::Whitespace),
+                TokenKind::LeftParenthesis | TokenKind::LeftCurly | TokenKind::StringHead(_) => {
+                    depth += 1;
+                    None
+                }
+                TokenKind::RightParenthesis | TokenKind::RightCurly | TokenKind::StringTail(_) => {
+                    depth = depth.saturating_sub(1);
+                    None
+                }
+                _ => None,
+            };
+            line_start = *kind == TokenKind::Newline && trivia.is_none();
+            match trivia {
+                Some(trivia) => (Lexeme::Trivia(trivia), span),
+                None => (lexeme, span),
+            }
+        })
         .collect()
 }
 
+/// A character of a block string, and whether it was written as is. Escaped
+/// characters are never indentation or line breaks.
+type BlockChar = (char, bool);
+
+/// Strips the indentation that the lines of a block string have in common,
+/// ignoring the blank ones. The line after the opening quotes is dropped
+/// when it is blank, and so is the indentation of the closing quotes, so that
+///
+/// ```text
+/// """
+///   a
+///     b
+///   """
+/// ```
+///
+/// is `"a\n  b\n"`. Interpolations stay where they are and count as text.
+fn dedent<T>(head: Vec<BlockChar>, rest: Vec<(T, Vec<BlockChar>)>) -> (String, Vec<(T, String)>) {
+    // Interpolations are `None`, between the texts
+    let mut items: Vec<Option<BlockChar>> = head.into_iter().map(Some).collect();
+    let mut interpolations = Vec::new();
+    for (interpolation, text) in rest {
+        interpolations.push(interpolation);
+        items.push(None);
+        items.extend(text.into_iter().map(Some));
+    }
+
+    let newline = Some(('\n', true));
+    let is_indentation = |item: &Option<BlockChar>| matches!(item, Some((' ' | '\t', true)));
+    let indentation =
+        |line: &[Option<BlockChar>]| line.iter().take_while(|i| is_indentation(i)).count();
+    let blank = |line: &[Option<BlockChar>]| indentation(line) == line.len();
+
+    let mut lines = items.split(|item| *item == newline).collect::<Vec<_>>();
+    if lines.len() > 1 && blank(lines[0]) {
+        lines.remove(0);
+    }
+    if let [.., _, last] = &mut lines[..] {
+        if blank(last) {
+            *last = &[];
+        }
+    }
+
+    let common = lines
+        .iter()
+        .filter(|line| !blank(line))
+        .map(|line| indentation(line))
+        .min()
+        .unwrap_or(0);
+    let items = lines
+        .iter()
+        .map(|line| &line[indentation(line).min(common)..])
+        .collect::<Vec<_>>()
+        .join(&newline);
+
+    let mut texts = items
+        .split(Option::is_none)
+        .map(|text| text.iter().flatten().map(|(c, _)| c).collect::<String>());
+    let head = texts.next().unwrap_or_default();
+    (head, interpolations.into_iter().zip(texts).collect())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::source::Sources;
     use chumsky::error;
     use insta::assert_debug_snapshot;
     use rstest::rstest;
     use tracing::debug;
     use tracing_test::traced_test;
 
-    type TestExtra = extra::Err<error::Cheap>;
+    type TestExtra = extra::Err<error::Cheap<Span>>;
 
     #[rstest]
     #[traced_test]
@@ -152,7 +669,7 @@ mod tests {
         )]
         input: &str,
     ) {
-        let p = symbol::<_, _, TestExtra>()
+        let p = symbol::<_, _, extra::Err<error::Cheap>>()
             .padded()
             .repeated()
             .collect::<Vec<_>>();
@@ -166,38 +683,70 @@ mod tests {
         #[values(
             ("empty", ""),
             ("int", "1 23 313 1"),
+            ("numbers", "0xff 0o17 0b1010 1_000 1.5 2.5e-3 1E3 1.a"),
+            ("prefixed exponent", "0x1e+1 0b1e"),
+            ("bad number", "1 + 0xZZ"),
+            ("bad decimal", "x = 1.5e"),
             ("symbols", "+ == != -"),
             ("symbols_split", r#"+3+1--3//3&(s++)"#),
             ("string", r#" "foo" "bar" "#),
             ("bad s", r#" "foo "#),
+            ("escapes", r#""a\"b\\c\n\t\u{1F600}" "\u{41}""#),
+            ("bad escape", r#""a\qb""#),
+            ("bad unicode", r#""\u{110000} \u{zz} \u41""#),
+            ("raw", r###"r"a\n" r#"say "hi""# r##"a"#b"## raw"###),
+            ("bad raw", r##"x = r#"abc"##),
+            ("interpolation", r#""a ${b} c ${f "d ${e}"}$\${x}""#),
+            ("interpolation braces", "\"${ { a: 1 }.a }\"\n\"${match x {\n  _ => 1\n}}\""),
+            ("unclosed interpolation", r#""a ${b"#),
+            ("block", "x = \"\"\"\n    [server]\n      port = 80\n\n    name = \"a\"\n    \"\"\"\ny"),
+            ("block interpolation", "\"\"\"\n  a ${\n    f x\n  } b\n    c\\t${y}\n  \"\"\""),
+            ("block escapes", "\"\"\"\n  \\u{20} a\n  \\q\"\"\""),
+            ("comments", "a = 1 # one\n# whole line\nb = #[ inline ]# 2\n#[ outer #[ inner ]#\n  still ]#c"),
+            ("doc comments", "## Adds one.\n##\ninc = x => x + 1 ## not this\nr = {\n  ## nor this\n  a: 1\n}"),
+            ("unterminated comment", "a #[ b #[ c ]# d"),
+            ("hash symbols", "a +# b\n##x"),
             ("parens", r#"(12 +23)()("foo")(1+1)"#),
             ("ident", "foo bar foo_bar foo-bar (foo+1)"),
             ("reserved", "():{},="),
             ("assign", "a=b"),
             ("assign2", "a=b==c"),
             ("arrow", "a=>b"),
-            ("arrow2", "a==>>b")
+            ("arrow2", "a==>>b"),
+            ("field", "a.b.c (f x).y"),
+            ("keywords", "if a then b else c; let x = 1 in iffy; type T = A"),
+            ("match", "match x { Some _ => 1, { a: \"b\" } => a_b }"),
+            ("separators", "a = 1; b = 2\nc\n  + d\n\n(e\nf)\n")
         )]
         input: (&str, &str),
     ) {
         let p = lexer::<TestExtra>().padded();
+        let input_ctx = input.1.with_context(SpanContext::default());
 
-        assert_debug_snapshot!(input.0, (input.0, input.1, p.parse(input.1)));
+        assert_debug_snapshot!(input.0, (input.0, input.1, p.parse(input_ctx)));
     }
 
     #[traced_test]
     #[test]
-    fn test_lexer_new() {
-        let p = lexer_new();
-        let res = p.parse("2 123 12".with_context(Default::default()));
-        debug!(?res);
-
-        for r in res.into_output().unwrap() {
-            // let x = r.1.context();
-            let c = r.0.context();
-            debug!(?r, ?c);
+    fn test_lexer_file() {
+        let mut sources = Sources::new();
+        sources.add("other.len", "");
+        let file = sources.add("main.len", "a = 1\nb = a + \"foo\"");
+        let input = sources.get(file).text();
+
+        let tokens = lexer::<TestExtra>()
+            .parse(input.with_context(file.context()))
+            .into_result()
+            .unwrap();
+        debug!(?tokens);
+
+        assert_eq!(tokens.len(), 9);
+        for (_, span) in &tokens {
+            assert_eq!(span.context().source, SpanSource::File(file));
         }
 
-        todo!();
+        let (kind, span) = &tokens[8];
+        assert_eq!(*kind, TokenKind::String(String::from("foo")));
+        assert_eq!(sources.locate(span).unwrap().to_string(), "main.len:2:9");
     }
 }
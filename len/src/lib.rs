@@ -1,9 +1,18 @@
 pub mod ast;
+pub mod class;
+pub mod complete;
+pub mod cst;
+pub mod diagnostic;
 pub mod eval;
+pub mod exhaustive;
+pub mod infer;
 pub mod lexer;
+pub mod number;
+pub mod prelude;
+pub mod source;
 pub mod ty;
-pub mod complete;
 
 pub type Int = num::BigInt;
+pub type Rational = num::BigRational;
 
-pub use chumsky;
\ No newline at end of file
+pub use chumsky;
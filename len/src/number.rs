@@ -0,0 +1,191 @@
+use crate::{Int, Rational};
+use num::{One, Zero};
+
+/// The value of a number literal.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Number {
+    Integer(Int),
+    /// A literal with a fraction or an exponent.
+    Decimal(Rational),
+}
+
+/// Parses the text of a number literal, as cut out by the lexer.
+///
+/// Integers can be written in hexadecimal, octal or binary with a `0x`, `0o`
+/// or `0b` prefix, decimals with a fraction and an exponent like `1.5e-3`,
+/// and both with `_` between the digits. The error is a message for the
+/// user.
+pub fn parse(text: &str) -> Result<Number, String> {
+    let (radix, name) = match text.get(..2) {
+        Some("0x") => (16, "hexadecimal"),
+        Some("0o") => (8, "octal"),
+        Some("0b") => (2, "binary"),
+        _ => return parse_decimal(text),
+    };
+
+    let digits = without_separators(&text[2..]);
+    if let Some(c) = digits.chars().find(|c| !c.is_digit(radix)) {
+        return Err(format!("invalid digit `{c}` in {name} literal `{text}`"));
+    }
+    if digits.is_empty() {
+        return Err(format!("{name} literal `{text}` has no digits"));
+    }
+
+    let n = Int::parse_bytes(digits.as_bytes(), radix).expect("the digits were checked");
+    Ok(Number::Integer(n))
+}
+
+fn parse_decimal(text: &str) -> Result<Number, String> {
+    let digits = without_separators(text);
+    let invalid = |c| Err(format!("invalid digit `{c}` in decimal literal `{text}`"));
+
+    let (mantissa, exponent) = match digits.split_once(['e', 'E']) {
+        Some((mantissa, exponent)) => (mantissa, Some(exponent)),
+        None => (digits.as_str(), None),
+    };
+    let (integer, fraction) = match mantissa.split_once('.') {
+        Some((integer, fraction)) => (integer, fraction),
+        None => (mantissa, ""),
+    };
+
+    if let Some(c) = integer
+        .chars()
+        .chain(fraction.chars())
+        .find(|c| !c.is_ascii_digit())
+    {
+        return invalid(c);
+    }
+
+    let Some(exponent) = exponent else {
+        if mantissa.contains('.') {
+            return Ok(Number::Decimal(decimal(integer, fraction, 0)));
+        }
+        let n = integer.parse().expect("the digits were checked");
+        return Ok(Number::Integer(n));
+    };
+
+    let magnitude = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+    if let Some(c) = magnitude.chars().find(|c| !c.is_ascii_digit()) {
+        return invalid(c);
+    }
+    if magnitude.is_empty() {
+        return Err(format!("the exponent of `{text}` has no digits"));
+    }
+    // Exact values need all the digits, so huge exponents would take forever
+    let Ok(magnitude) = magnitude.parse::<u16>() else {
+        return Err(format!("the exponent of `{text}` is too large"));
+    };
+    let exponent = match exponent.starts_with('-') {
+        true => -i32::from(magnitude),
+        false => i32::from(magnitude),
+    };
+
+    Ok(Number::Decimal(decimal(integer, fraction, exponent)))
+}
+
+fn without_separators(text: &str) -> String {
+    text.chars().filter(|c| *c != '_').collect()
+}
+
+/// `integer.fraction * 10^exponent`, from checked digits.
+fn decimal(integer: &str, fraction: &str, exponent: i32) -> Rational {
+    let numerator: Int = format!("{integer}{fraction}")
+        .parse()
+        .expect("the digits were checked");
+    let shift = exponent - fraction.len() as i32;
+    let scale = num::pow(Int::from(10), shift.unsigned_abs() as usize);
+
+    match shift < 0 {
+        true => Rational::new(numerator, scale),
+        false => Rational::from_integer(numerator * scale),
+    }
+}
+
+/// Writes a rational as a decimal when it has a finite one, like `2.5` or
+/// `3`, and as a fraction like `1/3` otherwise.
+pub fn format_rational(r: &Rational) -> String {
+    let (numerator, denominator) = (r.numer(), r.denom());
+    if denominator.is_one() {
+        return numerator.to_string();
+    }
+
+    // A fraction in lowest terms has a finite decimal when its denominator
+    // only has the prime factors 2 and 5
+    let (mut rest, mut twos, mut fives) = (denominator.clone(), 0usize, 0usize);
+    while (&rest % 2u8).is_zero() {
+        rest /= 2u8;
+        twos += 1;
+    }
+    while (&rest % 5u8).is_zero() {
+        rest /= 5u8;
+        fives += 1;
+    }
+    if !rest.is_one() {
+        return format!("{numerator}/{denominator}");
+    }
+
+    let places = twos.max(fives);
+    let scaled = numerator * num::pow(Int::from(10), places) / denominator;
+    let sign = if scaled < Int::zero() { "-" } else { "" };
+    let digits = format!("{:0>width$}", scaled.magnitude(), width = places + 1);
+    let (integer, fraction) = digits.split_at(digits.len() - places);
+
+    format!("{sign}{integer}.{fraction}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("0", "0")]
+    #[case("1_000_000", "1000000")]
+    #[case("0xff", "255")]
+    #[case("0x_FF_FF", "65535")]
+    #[case("0o755", "493")]
+    #[case("0b1010_0101", "165")]
+    #[case("1.5", "1.5")]
+    #[case("1_000.25", "1000.25")]
+    #[case("2.50", "2.5")]
+    #[case("3.0", "3")]
+    #[case("1e3", "1000")]
+    #[case("1.5E+2", "150")]
+    #[case("25e-3", "0.025")]
+    #[case("0.1e-1", "0.01")]
+    #[case("0xZZ", "invalid digit `Z` in hexadecimal literal `0xZZ`")]
+    #[case("0b102", "invalid digit `2` in binary literal `0b102`")]
+    #[case("0o", "octal literal `0o` has no digits")]
+    #[case("0x__", "hexadecimal literal `0x__` has no digits")]
+    #[case("12abc", "invalid digit `a` in decimal literal `12abc`")]
+    #[case("1.2.3", "invalid digit `.` in decimal literal `1.2.3`")]
+    #[case("1e", "the exponent of `1e` has no digits")]
+    #[case("1e+-2", "invalid digit `-` in decimal literal `1e+-2`")]
+    #[case("1e99999", "the exponent of `1e99999` is too large")]
+    fn test_parse(#[case] text: &str, #[case] expected: &str) {
+        let parsed = match parse(text) {
+            Ok(Number::Integer(n)) => n.to_string(),
+            Ok(Number::Decimal(r)) => format_rational(&r),
+            Err(message) => message,
+        };
+
+        assert_eq!(parsed, expected);
+    }
+
+    #[rstest]
+    #[case(1, 3, "1/3")]
+    #[case(-1, 4, "-0.25")]
+    #[case(-5, 2, "-2.5")]
+    #[case(7, 1, "7")]
+    #[case(1, 80, "0.0125")]
+    #[case(2, 6, "1/3")]
+    fn test_format_rational(
+        #[case] numerator: i32,
+        #[case] denominator: i32,
+        #[case] expected: &str,
+    ) {
+        let r = Rational::new(numerator.into(), denominator.into());
+
+        assert_eq!(format_rational(&r), expected);
+    }
+}
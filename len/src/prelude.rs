@@ -0,0 +1,33 @@
+use crate::ast::{self, TypeDecl};
+use crate::lexer::{lexer, SpanContext};
+use chumsky::{error::Rich, extra, input::Input, Parser};
+
+/// Types that every program can use, declared in the language itself.
+const PRELUDE: &str = "type List a = Nil | Cons (a, List a)";
+
+/// The declarations of the prelude, for [`crate::infer::Env::std`] and
+/// [`crate::eval::Scope::std`] to agree on.
+pub fn types() -> Vec<TypeDecl> {
+    let context = SpanContext::default();
+    let tokens = lexer::<extra::Err<Rich<_, _>>>()
+        .parse(PRELUDE.with_context(context))
+        .into_result()
+        .expect("the prelude is made of valid tokens");
+    let eoi = context.span(PRELUDE.len()..PRELUDE.len());
+    let program = ast::program_parser::<extra::Err<Rich<_, _>>>()
+        .parse(tokens.as_slice().spanned(eoi))
+        .into_result()
+        .expect("the prelude is a valid program");
+    program.types
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::class::LIST;
+
+    #[test]
+    fn test_list() {
+        assert!(types().iter().any(|decl| decl.name.name == LIST));
+    }
+}
@@ -0,0 +1,143 @@
+use crate::lexer::{Span, SpanContext, SpanSource};
+use chumsky::span::Span as _;
+use std::fmt;
+
+/// Index of a file registered in [`Sources`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FileId(usize);
+
+#[derive(Debug)]
+pub struct SourceFile {
+    name: String,
+    text: String,
+    /// Byte offset where every line starts.
+    line_starts: Vec<usize>,
+}
+
+impl SourceFile {
+    fn new(name: String, text: String) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+
+        Self {
+            name,
+            text,
+            line_starts,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// 0-based line and column (counted in characters) of a byte offset.
+    pub fn line_col(&self, offset: usize) -> (usize, usize) {
+        let offset = offset.min(self.text.len());
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        let column = self.text[self.line_starts[line]..offset].chars().count();
+        (line, column)
+    }
+
+    /// Text of a 0-based line, without the line break.
+    pub fn line(&self, line: usize) -> &str {
+        let start = self.line_starts[line];
+        let end = self
+            .line_starts
+            .get(line + 1)
+            .copied()
+            .unwrap_or(self.text.len());
+        self.text[start..end].trim_end_matches(['\n', '\r'])
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+}
+
+/// Every file that has been loaded, so that spans can be traced back to the
+/// text they came from.
+#[derive(Debug, Default)]
+pub struct Sources {
+    files: Vec<SourceFile>,
+}
+
+impl Sources {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, name: impl Into<String>, text: impl Into<String>) -> FileId {
+        self.files.push(SourceFile::new(name.into(), text.into()));
+        FileId(self.files.len() - 1)
+    }
+
+    pub fn get(&self, id: FileId) -> &SourceFile {
+        &self.files[id.0]
+    }
+
+    /// The file a span points into, if it was lexed from one of these sources.
+    pub fn file(&self, span: &Span) -> Option<&SourceFile> {
+        match span.context().source {
+            SpanSource::File(id) => self.files.get(id.0),
+            SpanSource::Unknown => None,
+        }
+    }
+
+    pub fn locate(&self, span: &Span) -> Option<Location<'_>> {
+        let file = self.file(span)?;
+        let (line, column) = file.line_col(span.start);
+
+        Some(Location {
+            file: file.name(),
+            line: line + 1,
+            column: column + 1,
+        })
+    }
+}
+
+impl FileId {
+    /// Context for the spans of tokens lexed from this file.
+    pub fn context(self) -> SpanContext {
+        SpanContext {
+            source: SpanSource::File(self),
+        }
+    }
+}
+
+/// A 1-based position in a source file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location<'a> {
+    pub file: &'a str,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for Location<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locate() {
+        let mut sources = Sources::new();
+        sources.add("a.len", "x = 1\n");
+        let b = sources.add("b.len", "x = 1\ny = \"é\" + x\n");
+
+        let span = b.context().span(17..18);
+        let location = sources.locate(&span).unwrap();
+
+        assert_eq!(location.to_string(), "b.len:2:11");
+        assert_eq!(sources.get(b).line(1), "y = \"é\" + x");
+        assert!(sources.locate(&SpanContext::default().span(0..1)).is_none());
+    }
+}
@@ -1,40 +1,358 @@
-use std::collections::HashMap;
+use crate::class::Class;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Write;
 
-#[derive(Debug)]
-enum Ty {
-    Product(HashMap<String, Option<Self>>),
-    Sum(HashMap<String, Option<Self>>),
+/// A type variable, only meaningful for the [`crate::infer`] run that created
+/// it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TyVar(pub u32);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Ty {
+    Var(TyVar),
     Primitive(Primitive),
+    Function(Box<Self>, Box<Self>),
+    Tuple(Vec<Self>),
+    /// A record, with its fields sorted by name. Records with a `rest` row
+    /// variable may have other fields too, as `{ name: String | r }`.
+    Product {
+        fields: BTreeMap<String, Self>,
+        rest: Option<TyVar>,
+    },
+    /// A sum type declared with `type`, applied to its arguments.
+    Sum {
+        name: String,
+        arguments: Vec<Self>,
+    },
 }
 
-#[derive(Debug)]
-enum Primitive {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Primitive {
     String,
     Int,
+    /// Exact fractions, from decimal literals.
+    Rational,
+    Bool,
+}
+
+impl Primitive {
+    pub fn name(self) -> &'static str {
+        match self {
+            Primitive::String => "String",
+            Primitive::Int => "Int",
+            Primitive::Rational => "Rational",
+            Primitive::Bool => "Bool",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "String" => Some(Primitive::String),
+            "Int" => Some(Primitive::Int),
+            "Rational" => Some(Primitive::Rational),
+            "Bool" => Some(Primitive::Bool),
+            _ => None,
+        }
+    }
+}
+
+impl Ty {
+    pub fn function(from: Ty, to: Ty) -> Self {
+        Ty::Function(Box::new(from), Box::new(to))
+    }
+
+    /// Type variables that appear in the type, in order of appearance.
+    pub fn variables(&self) -> Vec<TyVar> {
+        let mut variables = Vec::new();
+        self.collect_variables(&mut variables);
+        variables
+    }
+
+    fn collect_variables(&self, variables: &mut Vec<TyVar>) {
+        match self {
+            Ty::Var(var) => {
+                if !variables.contains(var) {
+                    variables.push(*var);
+                }
+            }
+            Ty::Primitive(_) => {}
+            Ty::Function(from, to) => {
+                from.collect_variables(variables);
+                to.collect_variables(variables);
+            }
+            Ty::Tuple(elements) => elements.iter().for_each(|t| t.collect_variables(variables)),
+            Ty::Product { fields, rest } => {
+                fields.values().for_each(|t| t.collect_variables(variables));
+                if let Some(rest) = rest {
+                    Ty::Var(*rest).collect_variables(variables);
+                }
+            }
+            Ty::Sum { arguments, .. } => arguments
+                .iter()
+                .for_each(|t| t.collect_variables(variables)),
+        }
+    }
+
+    /// Replaces the variables found in `mapping`.
+    pub fn substitute(&self, mapping: &HashMap<TyVar, Ty>) -> Ty {
+        match self {
+            Ty::Var(var) => mapping.get(var).cloned().unwrap_or_else(|| self.clone()),
+            Ty::Primitive(_) => self.clone(),
+            Ty::Function(from, to) => {
+                Ty::function(from.substitute(mapping), to.substitute(mapping))
+            }
+            Ty::Tuple(elements) => {
+                Ty::Tuple(elements.iter().map(|t| t.substitute(mapping)).collect())
+            }
+            Ty::Product { fields, rest } => {
+                let mut fields = fields
+                    .iter()
+                    .map(|(name, t)| (name.clone(), t.substitute(mapping)))
+                    .collect::<BTreeMap<_, _>>();
+                // The row of a record can only be replaced by another row
+                let rest = match rest.map(|rest| Ty::Var(rest).substitute(mapping)) {
+                    Some(Ty::Var(rest)) => Some(rest),
+                    Some(Ty::Product { fields: more, rest }) => {
+                        fields.extend(more);
+                        rest
+                    }
+                    _ => None,
+                };
+                Ty::Product { fields, rest }
+            }
+            Ty::Sum { name, arguments } => Ty::Sum {
+                name: name.clone(),
+                arguments: arguments.iter().map(|t| t.substitute(mapping)).collect(),
+            },
+        }
+    }
+}
+
+/// A type that is polymorphic over `variables`, like `a -> a`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Scheme {
+    pub variables: Vec<TyVar>,
+    /// Fields that the rows of some of the `variables` can't have, like the
+    /// row of `r` in `r => { a: 1 | r }`, which can't have `a` already.
+    pub lacks: BTreeMap<TyVar, BTreeSet<String>>,
+    /// The classes that some of the `variables` must be in, like `Eq` for
+    /// `a` in `Eq a => a -> a -> Bool`.
+    pub classes: BTreeMap<TyVar, BTreeSet<Class>>,
+    pub ty: Ty,
+}
+
+impl Scheme {
+    /// A type that isn't polymorphic.
+    pub fn mono(ty: Ty) -> Self {
+        Self {
+            variables: Vec::new(),
+            lacks: BTreeMap::new(),
+            classes: BTreeMap::new(),
+            ty,
+        }
+    }
+}
+
+/// Prints types, naming their variables `a`, `b`, ... in the order in which
+/// it first sees them, so that the types printed by the same printer agree
+/// on the names.
+#[derive(Debug, Default)]
+pub struct TyPrinter {
+    names: HashMap<TyVar, String>,
+}
+
+impl TyPrinter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn print(&mut self, ty: &Ty) -> String {
+        let mut out = String::new();
+        self.write(&mut out, ty, false);
+        out
+    }
+
+    fn name(&mut self, var: TyVar) -> String {
+        let next = self.names.len();
+        self.names
+            .entry(var)
+            .or_insert_with(|| {
+                let letter = char::from(b'a' + (next % 26) as u8);
+                match next / 26 {
+                    0 => letter.to_string(),
+                    n => format!("{letter}{n}"),
+                }
+            })
+            .clone()
+    }
+
+    /// `nested` types are arguments of a type constructor, where functions
+    /// and applied constructors need parentheses.
+    fn write(&mut self, out: &mut String, ty: &Ty, nested: bool) {
+        match ty {
+            Ty::Var(var) => out.push_str(&self.name(*var)),
+            Ty::Primitive(primitive) => out.push_str(primitive.name()),
+            Ty::Function(from, to) => {
+                if nested {
+                    out.push('(');
+                }
+                let parenthesize = matches!(**from, Ty::Function(..));
+                if parenthesize {
+                    out.push('(');
+                }
+                self.write(out, from, false);
+                if parenthesize {
+                    out.push(')');
+                }
+                out.push_str(" -> ");
+                self.write(out, to, false);
+                if nested {
+                    out.push(')');
+                }
+            }
+            Ty::Tuple(elements) => {
+                out.push('(');
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        out.push_str(", ");
+                    }
+                    self.write(out, element, false);
+                }
+                out.push(')');
+            }
+            Ty::Product { fields, rest: None } if fields.is_empty() => out.push_str("{}"),
+            Ty::Product { fields, rest } => {
+                out.push('{');
+                for (i, (name, field)) in fields.iter().enumerate() {
+                    let sep = if i == 0 { " " } else { ", " };
+                    let _ = write!(out, "{sep}{name}: ");
+                    self.write(out, field, false);
+                }
+                if let Some(rest) = rest {
+                    let _ = write!(out, " | {}", self.name(*rest));
+                }
+                out.push_str(" }");
+            }
+            Ty::Sum { name, arguments } if arguments.is_empty() => out.push_str(name),
+            Ty::Sum { name, arguments } => {
+                if nested {
+                    out.push('(');
+                }
+                out.push_str(name);
+                for argument in arguments {
+                    out.push(' ');
+                    self.write(out, argument, true);
+                }
+                if nested {
+                    out.push(')');
+                }
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Ty {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", TyPrinter::new().print(self))
+    }
+}
+
+/// Prints the classes of the variables before the type, as
+/// `(Add a, Eq b) => a -> b -> a`.
+impl std::fmt::Display for Scheme {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut printer = TyPrinter::new();
+        let ty = printer.print(&self.ty);
+
+        let mut constraints = self
+            .classes
+            .iter()
+            .flat_map(|(var, classes)| classes.iter().map(move |class| (*var, *class)))
+            .map(|(var, class)| (printer.name(var), class))
+            .collect::<Vec<_>>();
+        constraints.sort();
+        let constraints = constraints
+            .iter()
+            .map(|(var, class)| format!("{class} {var}"))
+            .collect::<Vec<_>>();
+
+        match constraints.as_slice() {
+            [] => write!(f, "{ty}"),
+            [constraint] => write!(f, "{constraint} => {ty}"),
+            constraints => write!(f, "({}) => {ty}", constraints.join(", ")),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use tracing::debug;
-    use tracing_test::traced_test;
-
-    // #[test]
-    // #[traced_test]
-    fn test_ty() {
-        let x = Ty::Primitive(Primitive::Int);
-
-        let y = Ty::Product(From::from([
-            // ..
-            (String::from("foo"), Some(Ty::Primitive(Primitive::String))),
-        ]));
-
-        let maybe_s = Ty::Sum(From::from([
-            (String::from("Some"), Some(Ty::Primitive(Primitive::Int))),
-            (String::from("None"), None),
-        ]));
-
-        debug!(?x, ?y);
-        todo!();
+    use rstest::rstest;
+
+    fn int() -> Ty {
+        Ty::Primitive(Primitive::Int)
+    }
+
+    fn option(ty: Ty) -> Ty {
+        Ty::Sum {
+            name: String::from("Option"),
+            arguments: vec![ty],
+        }
+    }
+
+    #[rstest]
+    #[case(Ty::function(int(), Ty::function(int(), int())), "Int -> Int -> Int")]
+    #[case(
+        Ty::function(Ty::function(Ty::Var(TyVar(7)), int()), Ty::Var(TyVar(3))),
+        "(a -> Int) -> b"
+    )]
+    #[case(
+        option(option(Ty::function(int(), int()))),
+        "Option (Option (Int -> Int))"
+    )]
+    #[case(Ty::Tuple(vec![int(), option(int())]), "(Int, Option Int)")]
+    #[case(
+        Ty::Product {
+            fields: From::from([
+                (String::from("foo"), Ty::Primitive(Primitive::String)),
+                (String::from("bar"), option(int())),
+            ]),
+            rest: None,
+        },
+        "{ bar: Option Int, foo: String }"
+    )]
+    #[case(
+        Ty::Product {
+            fields: From::from([(String::from("name"), Ty::Var(TyVar(4)))]),
+            rest: Some(TyVar(2)),
+        },
+        "{ name: a | b }"
+    )]
+    #[case(Ty::Product { fields: BTreeMap::new(), rest: None }, "{}")]
+    #[case(Ty::Product { fields: BTreeMap::new(), rest: Some(TyVar(0)) }, "{ | a }")]
+    fn test_display(#[case] ty: Ty, #[case] expected: &str) {
+        assert_eq!(ty.to_string(), expected);
+    }
+
+    #[rstest]
+    #[case(&[], "a -> b -> a")]
+    #[case(&[(3, Class::Eq)], "Eq b => a -> b -> a")]
+    #[case(
+        &[(3, Class::Eq), (5, Class::Add), (5, Class::Eq)],
+        "(Add a, Eq a, Eq b) => a -> b -> a"
+    )]
+    fn test_display_scheme(#[case] classes: &[(u32, Class)], #[case] expected: &str) {
+        let mut scheme = Scheme::mono(Ty::function(
+            Ty::Var(TyVar(5)),
+            Ty::function(Ty::Var(TyVar(3)), Ty::Var(TyVar(5))),
+        ));
+        for (var, class) in classes {
+            scheme
+                .classes
+                .entry(TyVar(*var))
+                .or_default()
+                .insert(*class);
+        }
+        assert_eq!(scheme.to_string(), expected);
     }
 }